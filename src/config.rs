@@ -1,5 +1,11 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::net::IpAddr;
+use std::num::NonZeroU32;
 use std::time::Duration;
+use crate::error::ProxyYoinkerError;
 
 
 // Optimized constants for Rust
@@ -13,6 +19,45 @@ pub const MAX_LINES: usize = 50000;
 pub const MAX_PROXIES_PER_CONFIG: usize = 2000;
 pub const MAX_HOSTPORT_MATCHES: usize = 5000;
 pub const MAX_JSON_MATCHES: usize = 1000;
+/// How deep the inline JSON scan looks inside a broken object for valid ones
+pub const MAX_JSON_DEPTH: usize = 128;
+/// Nodes one Clash `ports: "20000-50000"` hopping range can turn into
+pub const MAX_PORTS_PER_PROXY: usize = 32;
+pub const MAX_FOLLOWED_URLS: usize = 1000;
+pub const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+pub const DNS_CACHE_TTL: Duration = Duration::from_secs(300);
+pub const MAX_DNS_CACHE_ENTRIES: usize = 50000;
+pub const MAX_DNS_WORKERS: usize = 50;
+pub const DEFAULT_USER_AGENT: &str = "clash-verge/1.0";
+pub const DEFAULT_URL_OUT: &str = "working_links.md";
+pub const DEFAULT_NODE_OUT: &str = "node_latencies.md";
+// only used inside --output-dir
+pub const DEFAULT_SUMMARY_OUT: &str = "summary.md";
+pub const DEFAULT_CLASH_OUT: &str = "clash.yaml";
+pub const DEFAULT_RAW_OUT: &str = "raw_links.txt";
+pub const DEFAULT_PROBE_URL: &str = "http://www.gstatic.com/generate_204";
+/// `{bytes}` gets replaced with the --speed-test size
+pub const DEFAULT_SPEED_TEST_URL: &str = "https://speed.cloudflare.com/__down?bytes={bytes}";
+pub const MAX_SPEED_TEST_BYTES: u64 = 50 * 1024 * 1024;
+pub const SPEED_TEST_TIMEOUT: Duration = Duration::from_secs(15);
+pub const DUP_STATS_TOP: usize = 10;
+/// Body the URL check reads for its latency, phase 2 fetches the whole thing anyway
+pub const MAX_CHECK_BODY_BYTES: usize = 64 * 1024;
+/// --confirm re-tests passes slower than this share of the node timeout
+pub const DEFAULT_CONFIRM_FRACTION: f64 = 0.8;
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(3600);
+/// reqwest's own default, kept so runs without --pool-idle-timeout behave as before
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// How often the run checks for Ctrl-C / --timeout-total
+pub const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// --adaptive worker tuning (AIMD)
+pub const ADAPTIVE_START_WORKERS: usize = 8;
+pub const ADAPTIVE_MIN_WORKERS: usize = 2;
+pub const ADAPTIVE_STEP: usize = 4;
+pub const ADAPTIVE_WINDOW: usize = 20;
+pub const ADAPTIVE_ERROR_THRESHOLD: f64 = 0.3;
 
 // ETA estimation constants
 pub const EST_URL_CHECK_TIME: f64 = 0.15;
@@ -21,29 +66,755 @@ pub const EST_PARSE_TIME: f64 = 0.2;
 pub const EST_NODE_TIME: f64 = 0.1;
 pub const EST_NODES_PER_SUB: f64 = 50.0;
 
-#[derive(Parser)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckMode {
+    /// Plain HTTP request to http://host:port
+    Http,
+    /// Bare TCP handshake
+    Tcp,
+    /// ICMP echo to the host, ignores the port (needs root or CAP_NET_RAW on most systems)
+    Ping,
+    /// TLS handshake to host:port, with the link's `sni=` when it has one. Certificates aren't checked.
+    Tls,
+}
+
+impl std::fmt::Display for CheckMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckMode::Http => write!(f, "http"),
+            CheckMode::Tcp => write!(f, "tcp"),
+            CheckMode::Ping => write!(f, "ping"),
+            CheckMode::Tls => write!(f, "tls"),
+        }
+    }
+}
+
+/// `--method`: which request `http_check` sends
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HttpMethod {
+    /// HEAD only
+    Head,
+    /// GET only, for servers that answer HEAD with 405
+    Get,
+    /// HEAD first, GET if that fails or comes back 4xx/5xx
+    Auto,
+}
+
+/// `--tiers`: latency cutoffs (ms) for the report's fast / ok / slow buckets
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Tiers {
+    pub fast: f64,
+    pub ok: f64,
+    pub slow: f64,
+}
+
+impl Default for Tiers {
+    fn default() -> Self {
+        Self { fast: 100.0, ok: 500.0, slow: 2000.0 }
+    }
+}
+
+impl std::str::FromStr for Tiers {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cutoffs: Vec<f64> = s
+            .split(',')
+            .map(|c| c.trim().parse::<f64>().map_err(|_| format!("{:?} isn't a number", c.trim())))
+            .collect::<Result<_, _>>()?;
+        match cutoffs[..] {
+            [fast, ok, slow] if 0.0 < fast && fast < ok && ok < slow => Ok(Self { fast, ok, slow }),
+            [_, _, _] => Err("cutoffs must be positive and increasing".to_string()),
+            _ => Err("expected three cutoffs, e.g. 100,500,2000".to_string()),
+        }
+    }
+}
+
+impl From<Tiers> for String {
+    fn from(tiers: Tiers) -> Self {
+        tiers.to_string()
+    }
+}
+
+impl TryFrom<String> for Tiers {
+    type Error = String;
+    
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::fmt::Display for Tiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.fast, self.ok, self.slow)
+    }
+}
+
+/// Names `--row-template` can use as `{name}`
+pub const ROW_PLACEHOLDERS: [&str; 10] = ["host", "port", "protocol", "name", "country", "status", "ttfb", "latency", "speed", "tier"];
+
+/// `--row-template`: the node report's row layout, e.g. `| {host}:{port} | {latency} |`.
+/// Parsing checks every `{placeholder}` is one of `ROW_PLACEHOLDERS`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct RowTemplate {
+    source: String,
+    pieces: Vec<TemplatePiece>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePiece {
+    Text(String),
+    Field(&'static str),
+}
+
+impl RowTemplate {
+    /// Fills each placeholder with `value(name)`
+    pub fn render(&self, value: impl Fn(&str) -> String) -> String {
+        self.pieces.iter().map(|piece| match piece {
+            TemplatePiece::Text(text) => text.clone(),
+            TemplatePiece::Field(name) => value(name),
+        }).collect()
+    }
+}
+
+impl std::str::FromStr for RowTemplate {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = Vec::new();
+        let mut rest = s;
+        while let Some(open) = rest.find('{') {
+            let Some(close) = rest[open..].find('}') else {
+                return Err(format!("unclosed {{ in {:?}", s));
+            };
+            let name = &rest[open + 1..open + close];
+            let Some(field) = ROW_PLACEHOLDERS.iter().find(|p| **p == name) else {
+                return Err(format!("unknown placeholder {{{}}}, expected one of {}", name, ROW_PLACEHOLDERS.join(", ")));
+            };
+            if open > 0 {
+                pieces.push(TemplatePiece::Text(rest[..open].to_string()));
+            }
+            pieces.push(TemplatePiece::Field(field));
+            rest = &rest[open + close + 1..];
+        }
+        if !rest.is_empty() {
+            pieces.push(TemplatePiece::Text(rest.to_string()));
+        }
+        if !pieces.iter().any(|p| matches!(p, TemplatePiece::Field(_))) {
+            return Err("the template needs at least one {placeholder}".to_string());
+        }
+        Ok(Self { source: s.to_string(), pieces })
+    }
+}
+
+impl std::fmt::Display for RowTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl From<RowTemplate> for String {
+    fn from(template: RowTemplate) -> Self {
+        template.source
+    }
+}
+
+impl TryFrom<String> for RowTemplate {
+    type Error = String;
+    
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// `--sample`: which nodes `--limit` keeps
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SampleMode {
+    /// The first N in parse order
+    First,
+    /// N picked at random
+    Random,
+}
+
+/// `--family`: which IP versions this machine can actually reach
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AddressFamily {
+    V4,
+    V6,
+    Both,
+}
+
+impl AddressFamily {
+    pub fn allows(self, ip: &IpAddr) -> bool {
+        match self {
+            AddressFamily::V4 => ip.is_ipv4(),
+            AddressFamily::V6 => ip.is_ipv6(),
+            AddressFamily::Both => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportFormat {
+    Md,
+    Json,
+    Csv,
+}
+
+/// Node report row order. Latency and status put failures last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    Host,
+    Port,
+    Latency,
+    Status,
+}
+
+/// Every flag can also come from a `PY_<FLAG>` env var (`PY_MAX_IO_WORKERS=50`), the command line wins
+#[derive(Parser, Serialize, Deserialize)]
 #[command(about = "Concurrent Subscription Node Latency Tester")]
 pub struct Args {
-    /// Input folder or file to scan
-    pub input: String,
+    /// Input folders, files or http(s) URLs to scan ("-" reads from stdin)
+    #[arg(required_unless_present_any = ["config", "nodes_file"], num_args = 1.., env = "PY_INPUT")]
+    pub input: Vec<String>,
     
     /// Output file for working URLs
-    #[arg(short = 'u', long, default_value = "working_links.md")]
+    #[arg(short = 'u', long, default_value = DEFAULT_URL_OUT, env = "PY_URL_OUT")]
     pub url_out: String,
     
     /// Output file for node latencies
-    #[arg(short = 'n', long, default_value = "node_latencies.md")]
+    #[arg(short = 'n', long, default_value = DEFAULT_NODE_OUT, env = "PY_NODE_OUT")]
     pub node_out: String,
     
-    /// Enable verbose logging
-    #[arg(short, long)]
+    /// Enable verbose logging (debug level, includes a line per URL/node)
+    #[arg(short, long, conflicts_with = "quiet", env = "PY_VERBOSE")]
     pub verbose: bool,
     
+    /// Only log errors
+    #[arg(short, long, env = "PY_QUIET")]
+    pub quiet: bool,
+    
     /// Maximum IO workers
-    #[arg(long, default_value_t = MAX_IO_WORKERS)]
+    #[arg(long, default_value_t = MAX_IO_WORKERS, env = "PY_MAX_IO_WORKERS")]
     pub max_io_workers: usize,
     
     /// Maximum parse workers
-    #[arg(long, default_value_t = MAX_PARSE_WORKERS)]
+    #[arg(long, default_value_t = MAX_PARSE_WORKERS, env = "PY_MAX_PARSE_WORKERS")]
     pub max_parse_workers: usize,
+    
+    /// How to check nodes (most proxy nodes don't speak plain HTTP)
+    #[arg(long, value_enum, default_value_t = CheckMode::Tcp, env = "PY_CHECK_MODE")]
+    pub check_mode: CheckMode,
+    
+    /// Request method for URL checks and HTTP node checks
+    #[arg(long, value_enum, default_value_t = HttpMethod::Auto, env = "PY_METHOD")]
+    pub method: HttpMethod,
+    
+    /// Node report format
+    #[arg(long, value_enum, default_value_t = ReportFormat::Md, env = "PY_FORMAT")]
+    pub format: ReportFormat,
+    
+    /// Timeout for subscription URL checks and fetches (seconds)
+    #[arg(long, default_value_t = URL_TIMEOUT.as_secs(), env = "PY_URL_TIMEOUT")]
+    pub url_timeout: u64,
+    
+    /// Timeout for each node check (seconds)
+    #[arg(long, default_value_t = NODE_TIMEOUT.as_secs(), env = "PY_NODE_TIMEOUT")]
+    pub node_timeout: u64,
+    
+    /// Timeout for parsing a single subscription (seconds)
+    #[arg(long, default_value_t = PARSE_TIMEOUT.as_secs(), env = "PY_PARSE_TIMEOUT")]
+    pub parse_timeout: u64,
+    
+    /// How many levels of subscription URLs found inside bodies to follow
+    #[arg(long, default_value_t = 0, env = "PY_FOLLOW_DEPTH")]
+    pub follow_depth: usize,
+    
+    /// Send a request through each node as an HTTP proxy instead of just poking it
+    #[arg(long, env = "PY_VERIFY_PROXY")]
+    pub verify_proxy: bool,
+    
+    /// Endpoint requested through the node in --verify-proxy mode (must answer 204), repeatable
+    #[arg(long = "probe-url", default_value = DEFAULT_PROBE_URL, env = "PY_PROBE_URL")]
+    pub probe_urls: Vec<String>,
+    
+    /// How many of the --probe-url endpoints a node has to pass to count as working
+    #[arg(long, default_value_t = 1, env = "PY_PROBE_QUORUM")]
+    pub probe_quorum: usize,
+    
+    /// In --verify-proxy mode, also reach each node through this http:// proxy and record the chained latency
+    #[arg(long, requires = "verify_proxy", env = "PY_UPSTREAM_PROXY")]
+    pub upstream_proxy: Option<String>,
+    
+    /// Extra attempts for failed URL/node checks (exponential backoff from 100ms)
+    #[arg(long, default_value_t = 0, env = "PY_RETRIES")]
+    pub retries: usize,
+    
+    /// Resolve hosts and test only one node per (IP, port)
+    #[arg(long, env = "PY_RESOLVE_DEDUP")]
+    pub resolve_dedup: bool,
+    
+    /// Also write working nodes as a Clash config to this file
+    #[arg(long, env = "PY_CLASH_OUT")]
+    pub clash_out: Option<String>,
+    
+    /// Also write the original links of reachable nodes to this file, one per line, ready to import
+    #[arg(long, env = "PY_RAW_OUT")]
+    pub raw_out: Option<String>,
+    
+    /// Also write each node result to this file as a JSON line the moment its check finishes
+    #[arg(long, env = "PY_NDJSON")]
+    pub ndjson: Option<String>,
+    
+    /// Only report nodes at or below this latency (ms)
+    #[arg(long, env = "PY_MAX_LATENCY")]
+    pub max_latency: Option<f64>,
+    
+    /// User-Agent sent when checking and fetching subscriptions
+    #[arg(long, default_value = DEFAULT_USER_AGENT, env = "PY_USER_AGENT")]
+    pub user_agent: String,
+    
+    /// Extra header sent when checking and fetching subscriptions, e.g. `--header "Authorization: Bearer x"` (repeatable)
+    #[arg(long = "header", value_name = "NAME: VALUE", env = "PY_HEADER")]
+    pub headers: Vec<String>,
+    
+    /// Seconds an idle keep-alive connection stays in the pool (0 drops them right away)
+    #[arg(long, default_value_t = DEFAULT_POOL_IDLE_TIMEOUT.as_secs(), env = "PY_POOL_IDLE_TIMEOUT")]
+    pub pool_idle_timeout: u64,
+    
+    /// Max idle connections kept per host (default: unlimited)
+    #[arg(long, env = "PY_POOL_MAX_IDLE_PER_HOST")]
+    pub pool_max_idle_per_host: Option<usize>,
+    
+    /// Add a time-to-first-byte column to the Markdown reports
+    #[arg(long, env = "PY_SHOW_TTFB")]
+    pub show_ttfb: bool,
+    
+    /// Latency cutoffs (ms) for the node report's fast,ok,slow tiers; anything slower or failed is dead
+    #[arg(long, default_value_t = Tiers::default(), env = "PY_TIERS")]
+    pub tiers: Tiers,
+    
+    /// Add a fast / ok / slow / dead tier column to the Markdown node report
+    #[arg(long, env = "PY_SHOW_TIER")]
+    pub show_tier: bool,
+    
+    /// Only keep nodes of these protocols, comma-separated (e.g. vmess,vless,trojan)
+    #[arg(long, value_delimiter = ',', env = "PY_PROTOCOLS")]
+    pub protocols: Vec<String>,
+    
+    /// Cache DNS lookups across node checks (tcp mode)
+    #[arg(long, default_value_t = true, action = ArgAction::Set, env = "PY_DNS_CACHE")]
+    pub dns_cache: bool,
+    
+    /// File of hostnames / CIDR blocks to skip, one per line
+    #[arg(long, env = "PY_EXCLUDE")]
+    pub exclude: Option<String>,
+    
+    /// Stop after parsing and write a node inventory without testing anything
+    #[arg(long, env = "PY_NO_NODE_TEST")]
+    pub no_node_test: bool,
+    
+    /// Write a Markdown summary with counts, percentages and latency percentiles
+    #[arg(long, env = "PY_SUMMARY_OUT")]
+    pub summary_out: Option<String>,
+    
+    /// Append node rows to the Markdown report as they finish (unsorted)
+    #[arg(long, env = "PY_STREAM")]
+    pub stream: bool,
+    
+    /// Redirects to follow per request (0 reports the 3xx as-is)
+    #[arg(long, default_value_t = 5, env = "PY_MAX_REDIRECTS")]
+    pub max_redirects: usize,
+    
+    /// Maximum concurrent node checks against any single host
+    #[arg(long, env = "PY_PER_HOST_LIMIT")]
+    pub per_host_limit: Option<usize>,
+    
+    /// Show one progress bar per phase
+    #[arg(long, env = "PY_PROGRESS")]
+    pub progress: bool,
+    
+    /// MaxMind .mmdb file used to add country/city to reachable nodes
+    #[arg(long, env = "PY_GEOIP")]
+    pub geoip: Option<String>,
+    
+    /// Cap on nodes taken from one subscription, 0 for no cap (defaults to each parser's built-in limit)
+    #[arg(long, env = "PY_MAX_NODES_PER_SUB")]
+    pub max_nodes_per_sub: Option<usize>,
+    
+    /// Merge into existing Markdown reports instead of overwriting them (rows dedupe on URL / host:port)
+    #[arg(long, env = "PY_APPEND")]
+    pub append: bool,
+    
+    /// Max outbound requests started per second across all IO workers (unset = no limit)
+    #[arg(long, env = "PY_RATE_LIMIT")]
+    pub rate_limit: Option<NonZeroU32>,
+    
+    /// Upstream proxy for our own HTTP requests (http://, https:// or socks5://, credentials allowed)
+    #[arg(long, env = "PY_PROXY")]
+    pub proxy: Option<String>,
+    
+    /// Node report order
+    #[arg(long, value_enum, default_value_t = SortKey::Host, env = "PY_SORT")]
+    pub sort: SortKey,
+    
+    /// Start the subscription URL/fetch phases with a few workers and grow towards
+    /// --max-io-workers while requests keep succeeding, halving on a spike of failures.
+    /// Node checks stay fixed since dead nodes are expected there
+    #[arg(long, env = "PY_ADAPTIVE")]
+    pub adaptive: bool,
+    
+    /// Exit non-zero when fewer than this many nodes are reachable (zero working URLs always fails)
+    #[arg(long, conflicts_with = "no_node_test", env = "PY_FAIL_UNDER")]
+    pub fail_under: Option<usize>,
+    
+    /// Only read files matching these comma-separated patterns when input is a directory (e.g. `*.txt,*.yaml`)
+    #[arg(long, env = "PY_GLOB")]
+    pub glob: Option<String>,
+    
+    /// Hard ceiling in seconds for the whole run; when it's hit, queued and in-flight work is dropped and partial results are written
+    #[arg(long, env = "PY_TIMEOUT_TOTAL")]
+    pub timeout_total: Option<u64>,
+    
+    /// Keep fetched subscription bodies here and reuse them on the next run instead of re-downloading
+    #[arg(long, env = "PY_CACHE_DIR")]
+    pub cache_dir: Option<String>,
+    
+    /// Seconds a --cache-dir entry stays fresh
+    #[arg(long, default_value_t = DEFAULT_CACHE_TTL.as_secs(), env = "PY_CACHE_TTL")]
+    pub cache_ttl: u64,
+    
+    /// Skip nodes without an address of this IP family (literal IPs are classified directly)
+    #[arg(long, value_enum, default_value_t = AddressFamily::Both, env = "PY_FAMILY")]
+    pub family: AddressFamily,
+    
+    /// Before testing, print how many parsed nodes were duplicates and which ones repeat most
+    #[arg(long, env = "PY_DUP_STATS")]
+    pub dup_stats: bool,
+    
+    /// At the end, print median latency and reachable nodes per subscription
+    #[arg(long, env = "PY_SOURCE_STATS")]
+    pub source_stats: bool,
+    
+    /// Also test the CDN host from a trojan link's `host=`/`sni=` param, next to the server itself
+    #[arg(long, env = "PY_EXPAND_CDN")]
+    pub expand_cdn: bool,
+    
+    /// Only test this many nodes (after dedup and filtering)
+    #[arg(long, env = "PY_LIMIT")]
+    pub limit: Option<usize>,
+    
+    /// Which nodes --limit keeps
+    #[arg(long, value_enum, default_value_t = SampleMode::First, requires = "limit", env = "PY_SAMPLE")]
+    pub sample: SampleMode,
+    
+    /// Test nodes in random order instead of parse order, so one provider's nodes don't all go at once
+    #[arg(long, env = "PY_SHUFFLE")]
+    pub shuffle: bool,
+    
+    /// Seed for --shuffle and --sample random, for reproducible runs
+    #[arg(long, env = "PY_SEED")]
+    pub seed: Option<u64>,
+    
+    /// Write a line per subscription (body size, parsers tried, why they found nothing) to this file
+    #[arg(long, env = "PY_DIAGNOSTICS")]
+    pub diagnostics: Option<String>,
+    
+    /// Skip fetched subscriptions smaller than this many bytes (tiny error pages) instead of parsing them
+    #[arg(long, default_value_t = 0, env = "PY_MIN_BODY_SIZE")]
+    pub min_body_size: usize,
+    
+    /// Write every report (links, nodes, summary, clash, raw links) into this directory
+    /// under its default name. Path flags that are set explicitly still win.
+    #[arg(long, env = "PY_OUTPUT_DIR")]
+    pub output_dir: Option<String>,
+    
+    /// Re-test nodes that failed or came close to the node timeout, keeping the better result
+    #[arg(long, env = "PY_CONFIRM")]
+    pub confirm: bool,
+    
+    /// With --confirm, passes slower than this fraction of the node timeout get re-tested too
+    #[arg(long, default_value_t = DEFAULT_CONFIRM_FRACTION, env = "PY_CONFIRM_FRACTION")]
+    pub confirm_fraction: f64,
+    
+    /// In --verify-proxy mode, download this many bytes through every working node and report MB/s
+    #[arg(long, value_name = "BYTES", requires = "verify_proxy", env = "PY_SPEED_TEST")]
+    pub speed_test: Option<u64>,
+    
+    /// Payload for --speed-test, `{bytes}` is replaced with the size
+    #[arg(long, default_value = DEFAULT_SPEED_TEST_URL, env = "PY_SPEED_TEST_URL")]
+    pub speed_test_url: String,
+    
+    /// Seconds the --speed-test download gets per node
+    #[arg(long, default_value_t = SPEED_TEST_TIMEOUT.as_secs(), env = "PY_SPEED_TEST_TIMEOUT")]
+    pub speed_test_timeout: u64,
+    
+    /// Regex for pulling subscription URLs out of the input, instead of `https?://[^\s)]+`.
+    /// With a capture group, the first group is the URL.
+    #[arg(long, env = "PY_URL_PATTERN")]
+    pub url_pattern: Option<String>,
+    
+    /// Cap on concurrent DNS lookups, separate from --max-io-workers
+    #[arg(long, default_value_t = MAX_DNS_WORKERS, env = "PY_MAX_DNS_WORKERS")]
+    pub max_dns_workers: usize,
+    
+    /// Gzip every report as it's written, adding `.gz` to the paths
+    #[arg(long, conflicts_with = "append", env = "PY_COMPRESS")]
+    pub compress: bool,
+    
+    /// Cap on retries across the whole run. Once it's spent nothing retries, whatever --retries says
+    #[arg(long, env = "PY_RETRY_BUDGET")]
+    pub retry_budget: Option<usize>,
+    
+    /// Layout for each node report row, e.g. "| {host}:{port} | {status} | {latency} |".
+    /// Placeholders: host, port, protocol, name, country, status, ttfb, latency, speed, tier
+    #[arg(long, conflicts_with = "append", env = "PY_ROW_TEMPLATE")]
+    pub row_template: Option<RowTemplate>,
+    
+    /// Open a fresh connection for every request, so latencies are cold even when nodes share a host
+    #[arg(long, conflicts_with = "pool_max_idle_per_host", env = "PY_NO_REUSE")]
+    pub no_reuse: bool,
+    
+    /// Test loopback, 0.0.0.0 and placeholder hosts (localhost, example.com) too, instead of skipping them
+    #[arg(long, env = "PY_KEEP_LOCAL")]
+    pub keep_local: bool,
+    
+    /// Leave failed nodes out of the node report. The console still logs every check
+    #[arg(long, conflicts_with = "no_node_test", env = "PY_ONLY_WORKING")]
+    pub only_working: bool,
+    
+    /// Test the nodes in this file directly, skipping the URL check, fetch and parse phases.
+    /// One `host:port` or share link (`vmess://...`) per line
+    #[arg(long, conflicts_with = "input", env = "PY_NODES_FILE")]
+    pub nodes_file: Option<String>,
+    
+    /// Connect once to every unique host before measuring, so no check pays for a cold DNS lookup.
+    /// Only helps the connect-based check modes (tcp, tls, ping) with the DNS cache on
+    #[arg(long, env = "PY_WARMUP")]
+    pub warmup: bool,
+    
+    /// JSON or TOML file (by extension) with any of these settings, keyed by their
+    /// snake_case names (`max_io_workers = 50`). Flags and PY_* env vars win over it.
+    #[arg(long, env = "PY_CONFIG")]
+    #[serde(skip)]
+    pub config: Option<String>,
+    
+    /// `-u` / `-n` set on purpose (flag, env or config file), even to the default name,
+    /// so `--output-dir` leaves them where they are
+    #[arg(skip)]
+    #[serde(skip)]
+    url_out_explicit: bool,
+    #[arg(skip)]
+    #[serde(skip)]
+    node_out_explicit: bool,
+}
+
+impl Args {
+    /// `Args::parse()` plus `--config`. Exits like clap on bad flags or `--help`.
+    pub fn parse_with_config() -> Result<Self, ProxyYoinkerError> {
+        Self::with_config(Self::command().get_matches())
+    }
+    
+    pub fn try_parse_with_config_from<I, T>(args: I) -> Result<Self, ProxyYoinkerError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().try_get_matches_from(args).map_err(|e| ProxyYoinkerError::Config(e.to_string()))?;
+        Self::with_config(matches)
+    }
+    
+    /// Fills every setting the command line and env left at its default from the `--config` file
+    fn with_config(matches: ArgMatches) -> Result<Self, ProxyYoinkerError> {
+        let explicit = |key: &str| matches!(matches.value_source(key), Some(ValueSource::CommandLine | ValueSource::EnvVariable));
+        let mut args = Self::from_arg_matches(&matches).map_err(|e| ProxyYoinkerError::Config(e.to_string()))?;
+        args.url_out_explicit = explicit("url_out");
+        args.node_out_explicit = explicit("node_out");
+        let Some(path) = args.config.clone() else {
+            return Ok(args);
+        };
+        
+        let text = std::fs::read_to_string(&path).map_err(|e| ProxyYoinkerError::input(&path, e))?;
+        let invalid = |e: &dyn std::fmt::Display| ProxyYoinkerError::Parse(format!("{}: {}", path, e));
+        let file: serde_json::Map<String, serde_json::Value> = if path.ends_with(".toml") {
+            toml::from_str(&text).map_err(|e| invalid(&e))?
+        } else {
+            serde_json::from_str(&text).map_err(|e| invalid(&e))?
+        };
+        
+        let in_file = |key: &str| file.contains_key(key);
+        let (url_out_explicit, node_out_explicit) = (explicit("url_out") || in_file("url_out"), explicit("node_out") || in_file("node_out"));
+        let mut merged = serde_json::to_value(&args).map_err(|e| invalid(&e))?;
+        for (key, value) in file {
+            let Some(slot) = merged.get_mut(&key) else {
+                return Err(ProxyYoinkerError::Config(format!("{}: unknown setting `{}`", path, key)));
+            };
+            if !explicit(&key) {
+                *slot = value;
+            }
+        }
+        
+        let args = Self {
+            config: Some(path.clone()),
+            url_out_explicit,
+            node_out_explicit,
+            ..serde_json::from_value(merged).map_err(|e| invalid(&e))?
+        };
+        if args.input.is_empty() && args.nodes_file.is_none() {
+            return Err(ProxyYoinkerError::Config(format!("no input: pass files/URLs or set `input` in {}", path)));
+        }
+        Ok(args)
+    }
+    
+    /// Points the report paths into `--output-dir`. Paths still at their default go into
+    /// the directory, reports that are off by default get turned on there. With `--compress`
+    /// every report path gets a `.gz`.
+    pub fn resolve_output_paths(mut self) -> Self {
+        if let Some(dir) = self.output_dir.clone() {
+            let in_dir = |name: &str| std::path::Path::new(&dir).join(name).to_string_lossy().into_owned();
+            
+            if !self.url_out_explicit && self.url_out == DEFAULT_URL_OUT {
+                self.url_out = in_dir(DEFAULT_URL_OUT);
+            }
+            if !self.node_out_explicit && self.node_out == DEFAULT_NODE_OUT {
+                self.node_out = in_dir(DEFAULT_NODE_OUT);
+            }
+            self.summary_out.get_or_insert_with(|| in_dir(DEFAULT_SUMMARY_OUT));
+            self.clash_out.get_or_insert_with(|| in_dir(DEFAULT_CLASH_OUT));
+            self.raw_out.get_or_insert_with(|| in_dir(DEFAULT_RAW_OUT));
+        }
+        
+        if self.compress {
+            let gz = |path: &mut String| {
+                if !path.ends_with(".gz") {
+                    path.push_str(".gz");
+                }
+            };
+            gz(&mut self.url_out);
+            gz(&mut self.node_out);
+            for path in [&mut self.summary_out, &mut self.clash_out, &mut self.raw_out, &mut self.ndjson, &mut self.diagnostics] {
+                path.as_mut().map(gz);
+            }
+        }
+        self
+    }
+    
+    /// Every report this run writes, in the order they get written
+    pub fn report_paths(&self) -> Vec<&str> {
+        // --nodes-file never gets to the URL report
+        let mut paths: Vec<&str> = self.nodes_file.is_none().then_some(self.url_out.as_str()).into_iter().collect();
+        paths.extend(self.diagnostics.as_deref());
+        paths.extend(self.ndjson.as_deref());
+        paths.push(&self.node_out);
+        paths.extend([&self.clash_out, &self.raw_out, &self.summary_out].into_iter().filter_map(|p| p.as_deref()));
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout_defaults() {
+        let args = Args::try_parse_from(["proxy-yoink-er", "links.txt"]).unwrap();
+        assert_eq!(args.url_timeout, URL_TIMEOUT.as_secs());
+        assert_eq!(args.node_timeout, NODE_TIMEOUT.as_secs());
+        assert_eq!(args.parse_timeout, PARSE_TIMEOUT.as_secs());
+    }
+
+    #[test]
+    fn test_config_file_with_cli_override() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        std::fs::write(&path, r#"{"input": ["links.txt"], "max_io_workers": 7, "check_mode": "http", "node_timeout": 9, "tiers": "50,200,800"}"#).unwrap();
+        let path = path.to_string_lossy().into_owned();
+        
+        let args = Args::try_parse_with_config_from(["proxy-yoink-er", "--config", &path, "--node-timeout", "3"]).unwrap();
+        assert_eq!(args.input, vec!["links.txt"]);
+        assert_eq!(args.max_io_workers, 7);
+        assert_eq!(args.check_mode, CheckMode::Http);
+        assert_eq!(args.tiers, "50,200,800".parse().unwrap());
+        // the flag wins over the file, untouched settings keep their defaults
+        assert_eq!(args.node_timeout, 3);
+        assert_eq!(args.url_timeout, URL_TIMEOUT.as_secs());
+        
+        std::fs::write(&path, r#"{"input": ["links.txt"], "max_io_wrokers": 7}"#).unwrap();
+        let Err(error) = Args::try_parse_with_config_from(["proxy-yoink-er", "--config", &path]) else { panic!("typo accepted") };
+        assert!(error.to_string().contains("max_io_wrokers"), "{}", error);
+    }
+
+    #[test]
+    fn test_config_file_toml() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "input = [\"a.txt\", \"b.txt\"]\nverify_proxy = true\nprobe_urls = [\"http://a.example.com/204\"]\n").unwrap();
+        
+        let args = Args::try_parse_with_config_from(["proxy-yoink-er", "--config", &path.to_string_lossy()]).unwrap();
+        assert_eq!(args.input, vec!["a.txt", "b.txt"]);
+        assert!(args.verify_proxy);
+        assert_eq!(args.probe_urls, vec!["http://a.example.com/204"]);
+    }
+
+    #[test]
+    fn test_row_template_placeholders() {
+        let template: RowTemplate = "{host}:{port} {status}".parse().unwrap();
+        assert_eq!(template.render(|name| name.to_uppercase()), "HOST:PORT STATUS");
+        assert!("| {host} | {colour} |".parse::<RowTemplate>().unwrap_err().contains("{colour}"));
+        assert!("| {host".parse::<RowTemplate>().is_err());
+        assert!("| plain |".parse::<RowTemplate>().is_err());
+    }
+
+    #[test]
+    fn test_nodes_file_replaces_input() {
+        let args = Args::try_parse_from(["proxy-yoink-er", "--nodes-file", "nodes.txt"]).unwrap();
+        assert_eq!(args.nodes_file.as_deref(), Some("nodes.txt"));
+        assert!(!args.report_paths().contains(&DEFAULT_URL_OUT));
+        assert!(Args::try_parse_from(["proxy-yoink-er", "links.txt", "--nodes-file", "nodes.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_compress_paths() {
+        let args = Args::try_parse_from(["proxy-yoink-er", "links.txt", "--compress", "--clash-out", "clash.yaml", "-n", "nodes.md.gz"]).unwrap().resolve_output_paths();
+        assert_eq!(args.report_paths(), vec![format!("{}.gz", DEFAULT_URL_OUT).as_str(), "nodes.md.gz", "clash.yaml.gz"]);
+        assert!(Args::try_parse_from(["proxy-yoink-er", "links.txt", "--compress", "--append"]).is_err());
+    }
+
+    #[test]
+    fn test_output_dir_paths() {
+        let args = Args::try_parse_from(["proxy-yoink-er", "links.txt", "--output-dir", "out", "-n", "custom.md"]).unwrap().resolve_output_paths();
+        let join = |name: &str| std::path::Path::new("out").join(name).to_string_lossy().into_owned();
+        assert_eq!(args.url_out, join(DEFAULT_URL_OUT));
+        assert_eq!(args.node_out, "custom.md");
+        assert_eq!(args.summary_out, Some(join(DEFAULT_SUMMARY_OUT)));
+        
+        let args = Args::try_parse_from(["proxy-yoink-er", "links.txt"]).unwrap().resolve_output_paths();
+        assert_eq!((args.url_out.as_str(), args.summary_out), (DEFAULT_URL_OUT, None));
+        
+        // asking for the default name by hand still means "here", not "in the directory"
+        let args = Args::try_parse_with_config_from(["proxy-yoink-er", "links.txt", "--output-dir", "out", "-u", DEFAULT_URL_OUT]).unwrap().resolve_output_paths();
+        assert_eq!(args.url_out, DEFAULT_URL_OUT);
+        assert_eq!(args.node_out, join(DEFAULT_NODE_OUT));
+    }
+
+    #[test]
+    fn test_timeout_overrides() {
+        let args = Args::try_parse_from([
+            "proxy-yoink-er", "links.txt",
+            "--url-timeout", "10",
+            "--node-timeout", "4",
+            "--parse-timeout", "30",
+        ]).unwrap();
+        assert_eq!(args.url_timeout, 10);
+        assert_eq!(args.node_timeout, 4);
+        assert_eq!(args.parse_timeout, 30);
+    }
 }
@@ -1,8 +1,29 @@
 use crate::models::RegexPatterns;
 
+/// Every match of `url_regex`, or of its first capture group when `--url-pattern` has one
 pub fn extract_urls(text: &str, patterns: &RegexPatterns) -> Vec<String> {
     patterns.url_regex
-        .find_iter(text)
+        .captures_iter(text)
+        .filter_map(|cap| cap.get(1).or_else(|| cap.get(0)))
         .map(|m| m.as_str().to_string())
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_url_pattern() {
+        let text = r#"{"subs": ["https://a.example.com/sub","https://b.example.com/sub?x=1"]}"#;
+        
+        let default = extract_urls(text, &RegexPatterns::new());
+        assert_eq!(default.len(), 1);
+        assert_ne!(default[0], "https://a.example.com/sub");
+        
+        let patterns = RegexPatterns::new().with_url_pattern(r#""(https?://[^"]+)""#).unwrap();
+        assert_eq!(extract_urls(text, &patterns), vec!["https://a.example.com/sub", "https://b.example.com/sub?x=1"]);
+        
+        assert!(RegexPatterns::new().with_url_pattern("https?://[").is_err());
+    }
+}
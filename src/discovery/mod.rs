@@ -2,6 +2,6 @@ pub mod extractor;
 pub mod searcher;
 
 pub use extractor::*;
-// pub use searcher::*;
+pub use searcher::*;
 
 // Link discovery orchestration functions go here
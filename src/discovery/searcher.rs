@@ -1 +1,147 @@
-// ill add searcher later
+use std::collections::HashSet;
+use std::future::Future;
+use crate::models::RegexPatterns;
+use crate::config::MAX_FOLLOWED_URLS;
+use crate::discovery::extractor::extract_urls;
+use crate::parsers::config_files::parse_clash_providers;
+use tracing::warn;
+
+/// Everything in a body that looks like another subscription to fetch
+pub fn discover_urls(body: &str, patterns: &RegexPatterns) -> Vec<String> {
+    let mut urls = extract_urls(body, patterns);
+    if body.contains("proxy-providers:") {
+        urls.extend(parse_clash_providers(body));
+    }
+    urls
+}
+
+/// Walks subscription bodies that are really just indexes of other subscriptions.
+/// Every level re-runs `discover_urls` on the freshly fetched bodies, `seen` makes sure
+/// nothing gets fetched twice (which also breaks cycles). Returns only the new bodies.
+pub async fn follow_nested_urls<F, Fut>(
+    bodies: &[(String, String)],
+    seen: &mut HashSet<String>,
+    patterns: &RegexPatterns,
+    max_depth: usize,
+    fetch: F,
+) -> Vec<(String, String)>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = (String, Option<String>)> + Send + 'static,
+{
+    let mut found = Vec::new();
+    let mut level: Vec<(String, String)> = bodies.to_vec();
+    let mut queued = 0;
+    
+    let mut capped = false;
+    
+    for _ in 0..max_depth {
+        let mut tasks = Vec::new();
+        'bodies: for (_, body) in &level {
+            for url in discover_urls(body, patterns) {
+                if queued >= MAX_FOLLOWED_URLS && !seen.contains(&url) {
+                    warn!("⚠️  Followed {} nested subscription URLs, skipping the rest", MAX_FOLLOWED_URLS);
+                    capped = true;
+                    break 'bodies;
+                }
+                if seen.insert(url.clone()) {
+                    queued += 1;
+                    tasks.push(tokio::spawn(fetch(url)));
+                }
+            }
+        }
+        
+        if tasks.is_empty() {
+            break;
+        }
+        
+        let mut next_level = Vec::new();
+        for task in tasks {
+            if let Ok((url, Some(body))) = task.await {
+                next_level.push((url, body));
+            }
+        }
+        
+        found.extend(next_level.iter().cloned());
+        level = next_level;
+        if capped {
+            break;
+        }
+    }
+    
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn mock_web() -> Arc<HashMap<String, String>> {
+        let mut web = HashMap::new();
+        web.insert("https://index.example.com/a".to_string(), "https://index.example.com/b\nvless://x@a.example.com:443".to_string());
+        // b links back to root, which must not be fetched again
+        web.insert("https://index.example.com/b".to_string(), "https://index.example.com/root\nhttps://index.example.com/c".to_string());
+        web.insert("https://index.example.com/c".to_string(), "trojan://x@c.example.com:443".to_string());
+        Arc::new(web)
+    }
+
+    async fn run(depth: usize) -> Vec<String> {
+        let web = mock_web();
+        let patterns = RegexPatterns::new();
+        let root = vec![("https://index.example.com/root".to_string(), "https://index.example.com/a".to_string())];
+        let mut seen: HashSet<String> = root.iter().map(|(u, _)| u.clone()).collect();
+        
+        let found = follow_nested_urls(&root, &mut seen, &patterns, depth, |url| {
+            let web = web.clone();
+            async move {
+                let body = web.get(&url).cloned();
+                (url, body)
+            }
+        }).await;
+        
+        let mut urls: Vec<String> = found.into_iter().map(|(u, _)| u).collect();
+        urls.sort();
+        urls
+    }
+
+    #[test]
+    fn test_discover_urls_includes_clash_providers() {
+        let body = "proxy-providers:\n  p1:\n    type: http\n    url: \"https://provider.example.com/p1.yaml\"\n";
+        let urls = discover_urls(body, &RegexPatterns::new());
+        assert!(urls.contains(&"https://provider.example.com/p1.yaml".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_follow_depth_zero_fetches_nothing() {
+        assert!(run(0).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_follow_depth_limits_levels() {
+        assert_eq!(run(1).await, vec!["https://index.example.com/a"]);
+        assert_eq!(run(2).await, vec!["https://index.example.com/a", "https://index.example.com/b"]);
+        assert_eq!(run(5).await, vec![
+            "https://index.example.com/a",
+            "https://index.example.com/b",
+            "https://index.example.com/c",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_follow_stops_at_url_cap() {
+        let links: String = (0..MAX_FOLLOWED_URLS + 5).map(|i| format!("https://sub{}.example.com/list\n", i)).collect();
+        let root = vec![("https://index.example.com/root".to_string(), links)];
+        let mut seen = HashSet::new();
+        
+        // every fetched body points one level further down, none of which may be followed
+        let found = follow_nested_urls(&root, &mut seen, &RegexPatterns::new(), 3, |url| async move {
+            let body = format!("{}/deeper", url);
+            (url, Some(body))
+        }).await;
+        
+        assert_eq!(found.len(), MAX_FOLLOWED_URLS);
+        assert!(found.iter().all(|(url, _)| !url.ends_with("/deeper")));
+    }
+}
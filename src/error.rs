@@ -0,0 +1,83 @@
+use thiserror::Error;
+
+/// Why a run (or loading its settings) failed, for callers that want to match on the cause
+#[derive(Debug, Error)]
+pub enum ProxyYoinkerError {
+    /// An input path, `--nodes-file`, `--exclude` or `--config` that's missing or unreadable
+    #[error("can't read input {path}: {source}")]
+    Input { path: String, source: std::io::Error },
+    /// Reading or writing anything else, mostly the reports
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Network(#[from] reqwest::Error),
+    /// A `--config` file or pattern that doesn't parse
+    #[error("{0}")]
+    Parse(String),
+    /// Bad flag values, or flags that don't go together
+    #[error("{0}")]
+    Config(String),
+    /// Anything else, e.g. a report that wouldn't serialize or a task that panicked
+    #[error("{0}")]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl ProxyYoinkerError {
+    pub fn input(path: impl Into<String>, source: std::io::Error) -> Self {
+        Self::Input { path: path.into(), source }
+    }
+}
+
+/// Boxed errors keep their IO/network cause when they have one
+impl From<Box<dyn std::error::Error + Send + Sync>> for ProxyYoinkerError {
+    fn from(error: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        let error = match error.downcast::<std::io::Error>() {
+            Ok(io) => return Self::Io(*io),
+            Err(error) => error,
+        };
+        match error.downcast::<reqwest::Error>() {
+            Ok(network) => Self::Network(*network),
+            Err(error) => Self::Other(error),
+        }
+    }
+}
+
+impl From<tokio::task::JoinError> for ProxyYoinkerError {
+    fn from(error: tokio::task::JoinError) -> Self {
+        Self::Other(Box::new(error))
+    }
+}
+
+impl From<serde_json::Error> for ProxyYoinkerError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Other(Box::new(error))
+    }
+}
+
+impl From<serde_yaml::Error> for ProxyYoinkerError {
+    fn from(error: serde_yaml::Error) -> Self {
+        Self::Other(Box::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boxed_errors_keep_their_cause() {
+        let io: Box<dyn std::error::Error + Send + Sync> = Box::new(std::io::Error::other("disk full"));
+        assert!(matches!(ProxyYoinkerError::from(io), ProxyYoinkerError::Io(_)));
+        
+        let other: Box<dyn std::error::Error + Send + Sync> = "something else".into();
+        let error = ProxyYoinkerError::from(other);
+        assert!(matches!(error, ProxyYoinkerError::Other(_)));
+        assert_eq!(error.to_string(), "something else");
+    }
+
+    #[test]
+    fn test_error_crosses_tasks() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<ProxyYoinkerError>();
+    }
+}
@@ -0,0 +1,146 @@
+use crate::config::{EST_FETCH_TIME, EST_NODES_PER_SUB, EST_NODE_TIME, EST_PARSE_TIME, EST_URL_CHECK_TIME};
+
+/// Share of subscription URLs that usually turn out to work
+const EST_WORKING_SHARE: f64 = 0.7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Urls,
+    Fetch,
+    Parse,
+    Nodes,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PhaseEstimate {
+    /// Expected item count, the real one once it's known
+    items: f64,
+    /// Wall seconds per item according to the `EST_*` constants
+    guess: f64,
+    /// Wall seconds the phase really took, once it's done
+    elapsed: Option<f64>,
+}
+
+/// Run time estimate. Starts from the `EST_*` constants, then every finished phase tells
+/// how far off they are on this machine/network and the phases still ahead get scaled to match.
+#[derive(Clone, Debug)]
+pub struct EtaEstimator {
+    phases: [PhaseEstimate; 4],
+}
+
+impl EtaEstimator {
+    pub fn new(num_urls: usize, io_workers: usize, parse_workers: usize) -> Self {
+        let urls = num_urls as f64;
+        let subs = urls * EST_WORKING_SHARE;
+        let (io, parse) = (io_workers.max(1) as f64, parse_workers.max(1) as f64);
+        let phase = |items, guess| PhaseEstimate { items, guess, elapsed: None };
+
+        Self {
+            phases: [
+                phase(urls, EST_URL_CHECK_TIME / io),
+                phase(subs, EST_FETCH_TIME / io),
+                phase(subs, EST_PARSE_TIME / parse),
+                phase(subs * EST_NODES_PER_SUB, EST_NODE_TIME / io),
+            ],
+        }
+    }
+
+    fn phase_mut(&mut self, phase: Phase) -> &mut PhaseEstimate {
+        &mut self.phases[phase as usize]
+    }
+
+    /// The real item count of a phase is known now (working URLs, parsed nodes, ...)
+    pub fn set_items(&mut self, phase: Phase, items: usize) {
+        self.phase_mut(phase).items = items as f64;
+    }
+
+    /// `phase` got through `items` in `elapsed` seconds
+    pub fn phase_done(&mut self, phase: Phase, items: usize, elapsed: f64) {
+        let estimate = self.phase_mut(phase);
+        estimate.items = items as f64;
+        estimate.elapsed = Some(elapsed);
+    }
+
+    /// Measured time over guessed time across the finished phases, 1.0 until there's data
+    fn slowdown(&self) -> f64 {
+        let (measured, guessed) = self
+            .phases
+            .iter()
+            .filter_map(|p| p.elapsed.map(|elapsed| (elapsed, p.items * p.guess)))
+            .fold((0.0, 0.0), |(m, g), (elapsed, guess)| (m + elapsed, g + guess));
+        if guessed > 0.0 { measured / guessed } else { 1.0 }
+    }
+
+    fn phase_remaining(&self, phase: &PhaseEstimate) -> f64 {
+        match phase.elapsed {
+            Some(_) => 0.0,
+            None => phase.items * phase.guess * self.slowdown(),
+        }
+    }
+
+    /// Seconds left for the phases that haven't finished
+    pub fn remaining(&self) -> f64 {
+        self.phases.iter().map(|p| self.phase_remaining(p)).sum()
+    }
+
+    /// Seconds left for node testing alone
+    pub fn node_phase(&self) -> f64 {
+        self.phase_remaining(&self.phases[Phase::Nodes as usize])
+    }
+
+    /// Time already spent plus what's left
+    pub fn total(&self) -> f64 {
+        self.phases.iter().filter_map(|p| p.elapsed).sum::<f64>() + self.remaining()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_converges_on_measured_speed() {
+        // everything really runs 3x slower than the constants say
+        let mut eta = EtaEstimator::new(100, 10, 10);
+        let actual = |phase: Phase, items: usize| {
+            let guess = EtaEstimator::new(100, 10, 10).phases[phase as usize].guess;
+            items as f64 * guess * 3.0
+        };
+        let true_remaining = actual(Phase::Fetch, 60) + actual(Phase::Parse, 60) + actual(Phase::Nodes, 3000);
+
+        eta.set_items(Phase::Fetch, 60);
+        eta.set_items(Phase::Parse, 60);
+        eta.set_items(Phase::Nodes, 3000);
+        let before = (eta.remaining() - actual(Phase::Urls, 100) - true_remaining).abs();
+        assert!(before > 1.0, "{}", before);
+
+        eta.phase_done(Phase::Urls, 100, actual(Phase::Urls, 100));
+        assert!((eta.remaining() - true_remaining).abs() < 1e-9);
+
+        eta.phase_done(Phase::Fetch, 60, actual(Phase::Fetch, 60));
+        eta.phase_done(Phase::Parse, 60, actual(Phase::Parse, 60));
+        assert!((eta.node_phase() - actual(Phase::Nodes, 3000)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mixed_speeds_narrow_the_error() {
+        // URL checks 2x slow, fetches 4x, parsing and node tests 3x
+        let guess = EtaEstimator::new(200, 20, 5);
+        let actual = |phase: Phase, factor: f64| {
+            let p = guess.phases[phase as usize];
+            p.items * p.guess * factor
+        };
+        let node_time = actual(Phase::Nodes, 3.0);
+        let mut eta = guess.clone();
+        let mut errors = vec![(eta.node_phase() - node_time).abs()];
+
+        for (phase, factor) in [(Phase::Urls, 2.0), (Phase::Fetch, 4.0), (Phase::Parse, 3.0)] {
+            let items = guess.phases[phase as usize].items as usize;
+            eta.phase_done(phase, items, actual(phase, factor));
+            errors.push((eta.node_phase() - node_time).abs());
+        }
+
+        assert!(errors[1] < errors[0], "{:?}", errors);
+        assert!(*errors.last().unwrap() < node_time * 0.1, "{:?}", errors);
+    }
+}
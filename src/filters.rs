@@ -0,0 +1,289 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::net::IpAddr;
+use ipnet::IpNet;
+use tokio::fs;
+use tracing::warn;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::config::{AddressFamily, SampleMode};
+use crate::error::ProxyYoinkerError;
+use crate::models::Node;
+use crate::network::dns::resolve_all;
+
+/// Hostnames and CIDR blocks that should never be tested
+#[derive(Debug, Default)]
+pub struct ExcludeList {
+    pub hosts: HashSet<String>,
+    pub nets: Vec<IpNet>,
+}
+
+impl ExcludeList {
+    /// One entry per line, `#` starts a comment. Bare IPs are treated as /32 or /128.
+    pub fn parse(text: &str) -> Self {
+        let mut list = Self::default();
+        
+        for line in text.lines() {
+            let entry = line.split('#').next().unwrap_or("").trim();
+            if entry.is_empty() {
+                continue;
+            }
+            if let Ok(net) = entry.parse::<IpNet>() {
+                list.nets.push(net);
+            } else if let Ok(ip) = entry.parse::<IpAddr>() {
+                list.nets.push(IpNet::from(ip));
+            } else {
+                list.hosts.insert(entry.to_lowercase());
+            }
+        }
+        
+        list
+    }
+    
+    pub async fn load(path: &str) -> Result<Self, ProxyYoinkerError> {
+        let text = fs::read_to_string(path).await.map_err(|e| ProxyYoinkerError::input(path, e))?;
+        Ok(Self::parse(&text))
+    }
+    
+    pub fn is_excluded(&self, host: &str, ip: Option<IpAddr>) -> bool {
+        if self.hosts.contains(&host.to_lowercase()) {
+            return true;
+        }
+        ip.is_some_and(|ip| self.nets.iter().any(|net| net.contains(&ip)))
+    }
+}
+
+/// Drops excluded nodes. Hosts are only resolved when there are CIDR rules to check.
+pub async fn filter_excluded<F, Fut>(nodes: Vec<Node>, exclude: &ExcludeList, resolve: F) -> Vec<Node>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Option<IpAddr>> + Send + 'static,
+{
+    let unresolved: Vec<Node> = nodes
+        .iter()
+        .filter(|n| n.host.parse::<IpAddr>().is_err())
+        .cloned()
+        .collect();
+    let resolved = if exclude.nets.is_empty() {
+        Default::default()
+    } else {
+        resolve_all(&unresolved, resolve).await
+    };
+    
+    nodes
+        .into_iter()
+        .filter(|node| {
+            let ip = node.host.parse::<IpAddr>().ok().or_else(|| resolved.get(&node.host).copied());
+            !exclude.is_excluded(&node.host, ip)
+        })
+        .collect()
+}
+
+/// Drops nodes with no address in `family`. Literal IPs are checked as-is, hostnames get
+/// resolved to every address they have. Hosts that don't resolve at all are kept.
+pub async fn filter_by_family<F, Fut>(nodes: Vec<Node>, family: AddressFamily, resolve: F) -> Vec<Node>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Vec<IpAddr>> + Send + 'static,
+{
+    if family == AddressFamily::Both {
+        return nodes;
+    }
+    
+    let hosts: HashSet<&String> = nodes.iter().filter(|n| n.host.parse::<IpAddr>().is_err()).map(|n| &n.host).collect();
+    let tasks: Vec<_> = hosts.into_iter().map(|host| (host.clone(), tokio::spawn(resolve(host.clone())))).collect();
+    let mut resolved = std::collections::HashMap::new();
+    for (host, task) in tasks {
+        resolved.insert(host, task.await.unwrap_or_default());
+    }
+    
+    nodes
+        .into_iter()
+        .filter(|node| match node.host.parse::<IpAddr>() {
+            Ok(ip) => family.allows(&ip),
+            Err(_) => {
+                let ips = resolved.get(&node.host).map(Vec::as_slice).unwrap_or_default();
+                ips.is_empty() || ips.iter().any(|ip| family.allows(ip))
+            }
+        })
+        .collect()
+}
+
+/// Hostnames template configs ship with instead of a real server
+const PLACEHOLDER_HOSTS: [&str; 4] = ["localhost", "example.com", "example.org", "example.net"];
+
+/// Loopback or unspecified IPs (`127.0.0.1`, `0.0.0.0`, `::1`) and placeholder names
+/// like `localhost` or `example.com`, none of which is a node worth testing
+pub fn is_local_or_placeholder(host: &str) -> bool {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        let ip = ip.to_canonical();
+        return ip.is_loopback() || ip.is_unspecified();
+    }
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    PLACEHOLDER_HOSTS.contains(&host.as_str()) || host.ends_with(".localhost")
+}
+
+/// Drops the nodes `is_local_or_placeholder` flags, skipped with `--keep-local`
+pub fn filter_local(nodes: Vec<Node>) -> Vec<Node> {
+    nodes.into_iter().filter(|node| !is_local_or_placeholder(&node.host)).collect()
+}
+
+/// `--min-body-size`: drops fetched `(url, body)` pairs too small to be a real subscription
+pub fn filter_small_bodies(bodies: Vec<(String, String)>, min_size: usize) -> Vec<(String, String)> {
+    bodies
+        .into_iter()
+        .filter(|(url, body)| {
+            let keep = body.len() >= min_size;
+            if !keep {
+                warn!("Skipping {} - body is only {} bytes (--min-body-size {})", url, body.len(), min_size);
+            }
+            keep
+        })
+        .collect()
+}
+
+/// The RNG behind `--shuffle` and `--sample random`, fixed by `--seed` when given
+pub fn node_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// `--limit`: keeps at most `limit` nodes, the first ones or a random sample
+pub fn limit_nodes<R: Rng>(mut nodes: Vec<Node>, limit: usize, sample: SampleMode, rng: &mut R) -> Vec<Node> {
+    if sample == SampleMode::Random && nodes.len() > limit {
+        nodes.shuffle(rng);
+    }
+    nodes.truncate(limit);
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn fake_resolve(host: String) -> Option<IpAddr> {
+        match host.as_str() {
+            "internal.example.com" => Some("10.1.2.3".parse().unwrap()),
+            _ => Some("203.0.113.7".parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_filter_small_bodies() {
+        let bodies = vec![
+            ("https://dead.example.com/sub".to_string(), "<h1>404</h1>".to_string()),
+            ("https://live.example.com/sub".to_string(), "vless://id@node.example.com:443#a\n".repeat(4)),
+        ];
+        let kept = filter_small_bodies(bodies.clone(), 64);
+        assert_eq!(kept, vec![bodies[1].clone()]);
+        assert_eq!(filter_small_bodies(bodies.clone(), 0), bodies);
+    }
+
+    #[test]
+    fn test_limit_nodes() {
+        let nodes: Vec<Node> = (1..=10).map(|i| Node::new(format!("n{}.example.com", i), 443)).collect();
+        let mut rng = node_rng(Some(7));
+        
+        let first = limit_nodes(nodes.clone(), 3, SampleMode::First, &mut rng);
+        assert_eq!(first, nodes[..3].to_vec());
+        
+        let random = limit_nodes(nodes.clone(), 3, SampleMode::Random, &mut rng);
+        assert_eq!(random.len(), 3);
+        assert!(random.iter().all(|n| nodes.contains(n)));
+        assert_eq!(random.iter().collect::<HashSet<_>>().len(), 3);
+        
+        assert_eq!(limit_nodes(nodes.clone(), 50, SampleMode::Random, &mut rng), nodes);
+    }
+
+    #[test]
+    fn test_seeded_shuffle_is_deterministic() {
+        let nodes: Vec<Node> = (1..=20).map(|i| Node::new(format!("n{}.example.com", i), 443)).collect();
+        let shuffled = |seed| {
+            let mut nodes = nodes.clone();
+            nodes.shuffle(&mut node_rng(Some(seed)));
+            nodes
+        };
+        
+        assert_eq!(shuffled(42), shuffled(42));
+        assert_ne!(shuffled(42), nodes);
+        assert_ne!(shuffled(42), shuffled(43));
+    }
+
+    #[tokio::test]
+    async fn test_exclude_exact_hostname() {
+        let exclude = ExcludeList::parse("# bad hosts\nBad.Example.com\n");
+        let nodes = vec![
+            Node::new("bad.example.com".to_string(), 443),
+            Node::new("good.example.com".to_string(), 443),
+        ];
+        let kept = filter_excluded(nodes, &exclude, fake_resolve).await;
+        assert_eq!(kept, vec![Node::new("good.example.com".to_string(), 443)]);
+    }
+
+    #[tokio::test]
+    async fn test_exclude_private_cidr() {
+        let exclude = ExcludeList::parse("10.0.0.0/8\n");
+        let nodes = vec![
+            Node::new("10.0.0.5".to_string(), 8080),
+            Node::new("internal.example.com".to_string(), 443),
+            Node::new("good.example.com".to_string(), 443),
+        ];
+        let kept = filter_excluded(nodes, &exclude, fake_resolve).await;
+        assert_eq!(kept, vec![Node::new("good.example.com".to_string(), 443)]);
+    }
+
+    async fn fake_resolve_all(host: String) -> Vec<IpAddr> {
+        match host.as_str() {
+            "v6only.example.com" => vec!["2001:db8::6".parse().unwrap()],
+            "dual.example.com" => vec!["203.0.113.9".parse().unwrap(), "2001:db8::9".parse().unwrap()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn family_nodes() -> Vec<Node> {
+        vec![
+            Node::new("v6only.example.com".to_string(), 443),
+            Node::new("2001:db8::1".to_string(), 443),
+            Node::new("dual.example.com".to_string(), 443),
+            Node::new("203.0.113.1".to_string(), 443),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_family_v4_skips_v6_only_nodes() {
+        let kept = filter_by_family(family_nodes(), AddressFamily::V4, fake_resolve_all).await;
+        assert_eq!(kept, vec![
+            Node::new("dual.example.com".to_string(), 443),
+            Node::new("203.0.113.1".to_string(), 443),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_family_both_keeps_everything() {
+        let kept = filter_by_family(family_nodes(), AddressFamily::Both, fake_resolve_all).await;
+        assert_eq!(kept, family_nodes());
+        let v6 = filter_by_family(family_nodes(), AddressFamily::V6, fake_resolve_all).await;
+        assert_eq!(v6.len(), 3);
+    }
+
+    #[test]
+    fn test_filter_local() {
+        let nodes = vec![
+            Node::new("127.0.0.1".to_string(), 1080),
+            Node::new("localhost".to_string(), 1080),
+            Node::new("0.0.0.0".to_string(), 443),
+            Node::new("::1".to_string(), 443),
+            Node::new("Example.com".to_string(), 443),
+            Node::new("real.example.com".to_string(), 443),
+            Node::new("203.0.113.7".to_string(), 443),
+        ];
+        
+        let hosts: Vec<String> = filter_local(nodes).into_iter().map(|n| n.host).collect();
+        assert_eq!(hosts, vec!["real.example.com", "203.0.113.7"]);
+        assert!(is_local_or_placeholder("::ffff:127.0.0.1"));
+        assert!(is_local_or_placeholder("app.localhost"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::future::Future;
+use std::net::IpAddr;
+use std::path::Path;
+use maxminddb::{geoip2, Reader};
+use crate::error::ProxyYoinkerError;
+use crate::models::{Node, NodeResult};
+use crate::network::dns::resolve_all;
+
+/// A MaxMind City/Country database loaded into memory
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ProxyYoinkerError> {
+        let reader = Reader::open_readfile(path).map_err(|e| ProxyYoinkerError::Parse(format!("not a usable MaxMind database: {}", e)))?;
+        Ok(Self { reader })
+    }
+    
+    /// `(country ISO code, English city name)`, `None` when the IP isn't in the database
+    pub fn lookup(&self, ip: IpAddr) -> Option<(Option<String>, Option<String>)> {
+        let city: geoip2::City = self.reader.lookup(ip).ok()?;
+        let country = city.country.and_then(|c| c.iso_code).map(str::to_string);
+        let city_name = city.city
+            .and_then(|c| c.names)
+            .and_then(|names| names.get("en").map(|n| n.to_string()));
+        Some((country, city_name))
+    }
+}
+
+/// Fills in `country`/`city` for every working result whose IP the database knows about
+pub async fn enrich_with_geoip<F, Fut>(results: &mut [NodeResult], geoip: &GeoIp, resolve: F)
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Option<IpAddr>> + Send + 'static,
+{
+    let unresolved: Vec<Node> = results
+        .iter()
+        .filter(|r| r.is_working() && r.node.host.parse::<IpAddr>().is_err())
+        .map(|r| r.node.clone())
+        .collect();
+    let resolved = resolve_all(&unresolved, resolve).await;
+    
+    for result in results.iter_mut().filter(|r| r.is_working()) {
+        let ip = result.node.host.parse::<IpAddr>().ok().or_else(|| resolved.get(&result.node.host).copied());
+        if let Some((country, city)) = ip.and_then(|ip| geoip.lookup(ip)) {
+            result.country = country;
+            result.city = city;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hand-built fixture: 8.8.8.0/24 -> US / Mountain View, 1.1.1.0/24 -> AU (no city)
+    const TEST_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/geoip-test.mmdb");
+
+    async fn fake_resolve(host: String) -> Option<IpAddr> {
+        match host.as_str() {
+            "dns.example.com" => "8.8.8.8".parse().ok(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_lookup_known_ip() {
+        let geoip = GeoIp::open(TEST_DB).unwrap();
+        assert_eq!(
+            geoip.lookup("8.8.8.8".parse().unwrap()),
+            Some((Some("US".to_string()), Some("Mountain View".to_string())))
+        );
+        assert_eq!(geoip.lookup("1.1.1.1".parse().unwrap()), Some((Some("AU".to_string()), None)));
+        assert_eq!(geoip.lookup("10.0.0.1".parse().unwrap()), None);
+    }
+
+    #[tokio::test]
+    async fn test_enrich_working_nodes_only() {
+        let geoip = GeoIp::open(TEST_DB).unwrap();
+        let mut results = vec![
+            NodeResult::new(Node::new("dns.example.com".to_string(), 443), Some(0), Some(10.0)),
+            NodeResult::new(Node::new("1.1.1.1".to_string(), 443), None, None),
+            NodeResult::new(Node::new("10.0.0.1".to_string(), 443), Some(0), Some(10.0)),
+        ];
+        
+        enrich_with_geoip(&mut results, &geoip, fake_resolve).await;
+        assert_eq!(results[0].country.as_deref(), Some("US"));
+        assert_eq!(results[0].city.as_deref(), Some("Mountain View"));
+        assert_eq!(results[1].country, None);
+        assert_eq!(results[2].country, None);
+    }
+
+    #[test]
+    fn test_missing_db_is_an_error() {
+        assert!(GeoIp::open("/nonexistent/geoip.mmdb").is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use tokio::fs;
+use crate::config::{SortKey, Tiers};
+use crate::error::ProxyYoinkerError;
+use crate::models::{Node, NodeResult, UrlResult};
+use crate::io::output::{write_node_report, write_url_report, ReportColumns};
+
+/// Cells of every data row in the first Markdown table, keyed by the header names
+fn read_table(content: &str) -> Vec<HashMap<String, String>> {
+    let split = |line: &str| -> Vec<String> {
+        line.trim().trim_matches('|').split('|').map(|c| c.trim().to_string()).collect()
+    };
+    
+    let mut lines = content.lines().filter(|l| l.trim_start().starts_with('|'));
+    let Some(header) = lines.next() else { return Vec::new() };
+    let names = split(header);
+    
+    lines
+        .skip(1) // the |:---|---:| alignment row
+        .map(|line| names.iter().cloned().zip(split(line)).collect())
+        .collect()
+}
+
+fn cell<T: std::str::FromStr>(row: &HashMap<String, String>, name: &str) -> Option<T> {
+    row.get(name).and_then(|v| v.parse().ok())
+}
+
+/// Rows of a report written by `write_url_report`
+pub fn parse_url_report(content: &str) -> Vec<UrlResult> {
+    read_table(content)
+        .into_iter()
+        .filter_map(|row| {
+            Some(UrlResult {
+                url: row.get("URL")?.clone(),
+                // only working URLs make it into the report
+                status: Some(200),
+                latency: cell(&row, "Latency (ms)"),
+                ttfb: cell(&row, "TTFB (ms)"),
+                failure: None,
+            })
+        })
+        .collect()
+}
+
+/// Rows of a report written by `write_node_report`
+pub fn parse_node_report(content: &str) -> Vec<NodeResult> {
+    read_table(content)
+        .into_iter()
+        .filter_map(|row| {
+            let mut node = Node::new(row.get("Host")?.clone(), cell(&row, "Port")?);
+            node.protocol = row.get("Protocol").filter(|p| *p != "—").cloned();
+            
+            let mut result = NodeResult::new(node, cell(&row, "Status"), cell(&row, "Latency (ms)"));
+            result.ttfb = cell(&row, "TTFB (ms)");
+            result.throughput = cell(&row, "Speed (MB/s)");
+            result.failure = cell(&row, "Status");
+            if let Some(location) = row.get("Country").filter(|c| *c != "—") {
+                match location.split_once(" (") {
+                    Some((country, city)) => {
+                        result.country = Some(country.to_string());
+                        result.city = Some(city.trim_end_matches(')').to_string());
+                    }
+                    None => result.country = Some(location.clone()),
+                }
+            }
+            Some(result)
+        })
+        .collect()
+}
+
+/// Merges `working_urls` into whatever report is already at `path` (new rows win on
+/// the same URL) and rewrites it sorted. A missing file is just a fresh write.
+pub async fn append_url_report(path: &str, working_urls: &[UrlResult], show_ttfb: bool) -> Result<(), ProxyYoinkerError> {
+    let existing = fs::read_to_string(path).await.map(|c| parse_url_report(&c)).unwrap_or_default();
+    
+    let mut merged: HashMap<String, UrlResult> = existing.into_iter().map(|r| (r.url.clone(), r)).collect();
+    merged.extend(working_urls.iter().map(|r| (r.url.clone(), r.clone())));
+    
+    let merged: Vec<UrlResult> = merged.into_values().collect();
+    write_url_report(path, &merged, show_ttfb).await
+}
+
+/// Same as `append_url_report` but for the node report, deduped on host:port
+pub async fn append_node_report(path: &str, node_results: &[NodeResult], columns: ReportColumns, sort: SortKey, tiers: &Tiers) -> Result<(), ProxyYoinkerError> {
+    let existing = fs::read_to_string(path).await.map(|c| parse_node_report(&c)).unwrap_or_default();
+    
+    let mut merged: HashMap<Node, NodeResult> = existing.into_iter().map(|r| (r.node.clone(), r)).collect();
+    merged.extend(node_results.iter().map(|r| (r.node.clone(), r.clone())));
+    
+    let merged: Vec<NodeResult> = merged.into_values().collect();
+    write_node_report(path, &merged, columns, sort, tiers, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_append_node_report_dedups_and_sorts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("append_nodes.md");
+        let path = path.to_str().unwrap();
+        let old = vec![
+            NodeResult::new(Node::new("c.example.com".to_string(), 443), Some(0), Some(30.0)),
+            NodeResult::new(Node::new("a.example.com".to_string(), 443), None, None),
+        ];
+        write_node_report(path, &old, ReportColumns::default(), SortKey::Host, &Tiers::default(), None).await.unwrap();
+        
+        let new = vec![
+            NodeResult::new(Node::new("b.example.com".to_string(), 8080), Some(0), Some(20.0)),
+            NodeResult::new(Node::new("a.example.com".to_string(), 443), Some(0), Some(10.0)),
+        ];
+        append_node_report(path, &new, ReportColumns::default(), SortKey::Host, &Tiers::default()).await.unwrap();
+        let content = fs::read_to_string(path).await.unwrap();
+        
+        let rows: Vec<&str> = content.lines().filter(|l| l.contains(".example.com")).collect();
+        assert_eq!(rows, vec![
+            "| a.example.com | 443 | 0 | 10.0 |",
+            "| b.example.com | 8080 | 0 | 20.0 |",
+            "| c.example.com | 443 | 0 | 30.0 |",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_append_url_report_to_missing_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("append_urls.md");
+        let path = path.to_str().unwrap();
+        let url = |u: &str, l: f64| UrlResult { url: u.to_string(), status: Some(200), latency: Some(l), ttfb: None, failure: None };
+        
+        append_url_report(path, &[url("https://b.example.com/sub", 50.0)], false).await.unwrap();
+        append_url_report(path, &[url("https://a.example.com/sub", 20.0), url("https://b.example.com/sub", 40.0)], false).await.unwrap();
+        let content = fs::read_to_string(path).await.unwrap();
+        
+        let rows: Vec<&str> = content.lines().filter(|l| l.contains("example.com")).collect();
+        assert_eq!(rows, vec![
+            "| https://a.example.com/sub | 20.0 |",
+            "| https://b.example.com/sub | 40.0 |",
+        ]);
+    }
+}
@@ -0,0 +1,108 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use tracing::{debug, warn};
+
+/// `--cache-dir`: fetched bodies on disk, one file per URL, reused while younger than `ttl`
+#[derive(Debug, Clone)]
+pub struct BodyCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl BodyCache {
+    pub async fn open(dir: &str, ttl: Duration) -> std::io::Result<Self> {
+        fs::create_dir_all(dir).await?;
+        Ok(Self { dir: PathBuf::from(dir), ttl })
+    }
+    
+    /// FNV-1a of the URL, std's hasher isn't guaranteed stable between Rust releases
+    fn path_for(&self, url: &str) -> PathBuf {
+        let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, b| (hash ^ u64::from(b)).wrapping_mul(0x100000001b3));
+        self.dir.join(format!("{:016x}.body", hash))
+    }
+    
+    pub async fn get(&self, url: &str) -> Option<String> {
+        let path = self.path_for(url);
+        let modified = fs::metadata(&path).await.ok()?.modified().ok()?;
+        let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+        if age > self.ttl {
+            return None;
+        }
+        fs::read_to_string(&path).await.ok()
+    }
+    
+    pub async fn put(&self, url: &str, body: &str) {
+        if let Err(e) = fs::write(self.path_for(url), body).await {
+            warn!("Couldn't cache body of {}: {}", url, e);
+        }
+    }
+}
+
+/// `fetch` unless `cache` already has a fresh copy of `url`; successful fetches get stored
+pub async fn fetch_cached<F, Fut>(cache: Option<&BodyCache>, url: String, fetch: F) -> (String, Option<String>)
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = (String, Option<String>)>,
+{
+    if let Some(cache) = cache {
+        if let Some(body) = cache.get(&url).await {
+            debug!("{} - Loaded {} chars from cache", url, body.len());
+            return (url, Some(body));
+        }
+    }
+    
+    let (url, body) = fetch(url).await;
+    if let (Some(cache), Some(body)) = (cache, &body) {
+        cache.put(&url, body).await;
+    }
+    (url, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_warm_cache_skips_fetch() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("cache").to_str().unwrap().to_string();
+        let cache = BodyCache::open(&dir, Duration::from_secs(60)).await.unwrap();
+        let calls = AtomicUsize::new(0);
+        let fetch = |url: String| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move { (url, Some("vless://id@node.example.com:443".to_string())) }
+        };
+        
+        let url = "https://sub.example.com/list".to_string();
+        let first = fetch_cached(Some(&cache), url.clone(), fetch).await;
+        let second = fetch_cached(Some(&cache), url.clone(), fetch).await;
+        
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_stale_or_missing_entries_fetch() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("cache").to_str().unwrap().to_string();
+        let cache = BodyCache::open(&dir, Duration::ZERO).await.unwrap();
+        let calls = AtomicUsize::new(0);
+        let fetch = |url: String| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move { (url, None::<String>) }
+        };
+        
+        // failed fetches aren't cached, and a zero TTL never serves from disk
+        fetch_cached(Some(&cache), "https://a.example.com".to_string(), fetch).await;
+        fetch_cached(Some(&cache), "https://a.example.com".to_string(), fetch).await;
+        cache.put("https://b.example.com", "body").await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let stale = cache.get("https://b.example.com").await;
+        
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(stale, None);
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tokio::fs::File;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Where a report goes: the file itself, or a gzip stream into it
+pub type ReportSink = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Gzips everything written through it into `inner`. flate2 only does blocking IO, so the
+/// compressed bytes collect in a buffer that gets drained into `inner` on the next write,
+/// flush or shutdown. Shutdown writes the gzip trailer, so it has to be called.
+pub struct GzipWriter<W> {
+    inner: W,
+    encoder: GzEncoder<Vec<u8>>,
+    finished: bool,
+}
+
+impl<W: AsyncWrite + Unpin> GzipWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, encoder: GzEncoder::new(Vec::new(), Compression::default()), finished: false }
+    }
+    
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let compressed = self.encoder.get_mut();
+        while !compressed.is_empty() {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, compressed))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            compressed.drain(..written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for GzipWriter<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        this.encoder.write_all(buf)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+    
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+    
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            this.encoder.try_finish()?;
+            this.finished = true;
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Creates the report at `path`, gzipped when it ends in `.gz` (see `--compress`)
+pub async fn create_report(path: &str) -> io::Result<ReportSink> {
+    let file = File::create(path).await?;
+    Ok(if path.ends_with(".gz") { Box::new(GzipWriter::new(file)) } else { Box::new(file) })
+}
+
+/// Writes `content` to `sink` and closes it
+pub async fn write_and_close<W: AsyncWrite + Unpin>(mut sink: W, content: &[u8]) -> io::Result<()> {
+    sink.write_all(content).await?;
+    sink.shutdown().await
+}
+
+/// The whole report in one go, what `fs::write` did before `--compress`
+pub async fn save_report(path: &str, content: impl AsRef<[u8]>) -> io::Result<()> {
+    write_and_close(create_report(path).await?, content.as_ref()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SortKey, Tiers};
+    use crate::io::output::{write_node_report, ReportColumns};
+    use crate::models::{Node, NodeResult};
+    use std::io::Read;
+
+    #[tokio::test]
+    async fn test_compressed_report_matches_plain() {
+        let results: Vec<NodeResult> = (0..50u16)
+            .map(|i| NodeResult::new(Node::new(format!("n{}.example.com", i), 1000 + i), Some(0), Some(f64::from(i))))
+            .collect();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let plain = dir.join("plain.md").to_string_lossy().into_owned();
+        let gzipped = format!("{}.gz", plain);
+        
+        write_node_report(&plain, &results, ReportColumns::default(), SortKey::Host, &Tiers::default(), None).await.unwrap();
+        write_node_report(&gzipped, &results, ReportColumns::default(), SortKey::Host, &Tiers::default(), None).await.unwrap();
+        
+        let expected = std::fs::read_to_string(&plain).unwrap();
+        let compressed = std::fs::read(&gzipped).unwrap();
+        
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(&compressed[..]).read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, expected);
+        assert!(compressed.len() < expected.len());
+    }
+
+    #[tokio::test]
+    async fn test_gzip_writer_many_small_writes() {
+        let mut writer = GzipWriter::new(Vec::new());
+        let mut expected = String::new();
+        for i in 0..500 {
+            let line = format!("line {}\n", i);
+            writer.write_all(line.as_bytes()).await.unwrap();
+            writer.flush().await.unwrap();
+            expected.push_str(&line);
+        }
+        writer.shutdown().await.unwrap();
+        
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(&writer.inner[..]).read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, expected);
+    }
+}
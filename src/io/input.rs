@@ -1,25 +1,319 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use reqwest::Client;
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
 use std::path::Path;
+use std::time::Duration;
 use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{debug, warn};
+use crate::error::ProxyYoinkerError;
+use crate::models::{Node, RegexPatterns};
+use crate::network::try_fetch_body;
+use crate::parsers::detect_format_and_parse;
+use crate::parsers::proxy_urls::split_host_port;
 
-pub async fn gather_text(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+/// Text of every input joined together. With `glob` set (`*.txt,*.yaml`), a directory
+/// input only contributes the files whose name matches.
+pub async fn gather_text(paths: &[String], client: &Client, url_timeout: Duration, glob: Option<&str>) -> Result<String, ProxyYoinkerError> {
+    let mut texts = Vec::new();
+    for path in paths {
+        texts.push(gather_one(path, client, url_timeout, glob).await?);
+    }
+    Ok(texts.join("\n"))
+}
+
+async fn gather_one(path: &str, client: &Client, url_timeout: Duration, glob: Option<&str>) -> Result<String, ProxyYoinkerError> {
+    // "-" means read everything from stdin, e.g. `cat links.txt | proxy-yoink-er -`
+    if path == "-" {
+        return read_text_from(tokio::io::stdin()).await.map_err(|e| ProxyYoinkerError::input(path, e));
+    }
+    
+    // A single master subscription hosted somewhere
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return Ok(try_fetch_body(client, path, url_timeout).await?);
+    }
+    
+    let unreadable = |e| ProxyYoinkerError::input(path, e);
+    let metadata = fs::metadata(path).await.map_err(unreadable)?;
     let path = Path::new(path);
     let mut texts = Vec::new();
     
-    if path.is_dir() {
-        let mut entries = fs::read_dir(path).await?;
-        while let Some(entry) = entries.next_entry().await? {
+    if metadata.is_dir() {
+        let glob = glob.map(build_glob_set).transpose()?;
+        let mut entries = fs::read_dir(path).await.map_err(unreadable)?;
+        while let Some(entry) = entries.next_entry().await.map_err(unreadable)? {
             let path = entry.path();
-            if path.is_file() {
-                if let Ok(content) = fs::read_to_string(&path).await {
-                    texts.push(content);
-                }
+            let matches = match (&glob, path.file_name()) {
+                (Some(glob), Some(name)) => glob.is_match(name),
+                _ => true,
+            };
+            if path.is_file() && matches {
+                texts.extend(read_input_file(&path).await);
             }
         }
     } else {
-        if let Ok(content) = fs::read_to_string(path).await {
-            texts.push(content);
-        }
+        texts.extend(read_input_file(path).await);
     }
     
     Ok(texts.join("\n"))
 }
+
+/// Comma-separated patterns, matched against file names
+fn build_glob_set(patterns: &str) -> Result<GlobSet, ProxyYoinkerError> {
+    let invalid = |pattern: &str, e: globset::Error| ProxyYoinkerError::Config(format!("invalid --glob {:?}: {}", pattern, e));
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        builder.add(Glob::new(pattern).map_err(|e| invalid(pattern, e))?);
+    }
+    builder.build().map_err(|e| invalid(patterns, e))
+}
+
+/// `--nodes-file`: one node per line, either `host:port` (`[::1]:443` for IPv6) or a share
+/// link run through the usual parsers. Blank and `#` lines are skipped, anything else that
+/// doesn't parse gets a warning. Duplicates are dropped, file order is kept.
+pub async fn load_nodes_file(path: &str, patterns: &RegexPatterns, protocols: &[String]) -> Result<Vec<Node>, ProxyYoinkerError> {
+    fs::metadata(path).await.map_err(|e| ProxyYoinkerError::input(path, e))?;
+    let text = read_input_file(Path::new(path)).await
+        .ok_or_else(|| ProxyYoinkerError::input(path, std::io::Error::other("not readable as text")))?;
+    let mut seen = HashSet::new();
+    let mut nodes = Vec::new();
+    
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = if line.contains("://") {
+            detect_format_and_parse(line, patterns, protocols, None).0
+        } else {
+            split_host_port(line).map(|(host, port)| Node::new(host.to_string(), port)).into_iter().collect()
+        };
+        if parsed.is_empty() {
+            warn!("⚠️ Skipping line {} of {}: no node in {:?}", number + 1, path, line);
+        }
+        nodes.extend(parsed.into_iter().map(|node| node.with_source(path)).filter(|node| seen.insert(node.clone())));
+    }
+    
+    Ok(nodes)
+}
+
+/// Plain files are decoded as text, `.gz` gets decompressed first and `.zip` contributes
+/// every entry that's valid text. Anything unreadable is skipped with a warning.
+async fn read_input_file(path: &Path) -> Option<String> {
+    let bytes = match fs::read(path).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("⚠️ Skipping {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    let bytes = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("gz") => {
+            let mut decompressed = Vec::new();
+            if let Err(e) = flate2::read::GzDecoder::new(bytes.as_slice()).read_to_end(&mut decompressed) {
+                warn!("⚠️ Skipping {}: {}", path.display(), e);
+                return None;
+            }
+            decompressed
+        }
+        Some("zip") => return read_zip_text(path, bytes),
+        _ => bytes,
+    };
+    
+    let (text, lossy) = decode_text(&bytes);
+    if lossy {
+        warn!("⚠️ {} isn't valid UTF-8/UTF-16, invalid bytes were replaced", path.display());
+    }
+    Some(text)
+}
+
+/// Honours a UTF-8/UTF-16 BOM (and strips it), otherwise decodes as UTF-8. The flag is
+/// set when invalid bytes had to be replaced.
+fn decode_text(bytes: &[u8]) -> (String, bool) {
+    let (text, _, lossy) = encoding_rs::UTF_8.decode(bytes);
+    (text.into_owned(), lossy)
+}
+
+fn read_zip_text(path: &Path, bytes: Vec<u8>) -> Option<String> {
+    let mut archive = match zip::ZipArchive::new(Cursor::new(bytes)) {
+        Ok(archive) => archive,
+        Err(e) => {
+            warn!("⚠️ Skipping {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    let mut texts = Vec::new();
+    for i in 0..archive.len() {
+        if let Ok(mut entry) = archive.by_index(i) {
+            let mut bytes = Vec::new();
+            if !entry.is_file() || entry.read_to_end(&mut bytes).is_err() {
+                continue;
+            }
+            // Binary entries fail to decode and just get skipped
+            match decode_text(&bytes) {
+                (text, false) => texts.push(text),
+                (_, true) => debug!("Skipping non-text zip entry {} in {}", entry.name(), path.display()),
+            }
+        }
+    }
+    Some(texts.join("\n"))
+}
+
+pub async fn read_text_from<R: AsyncRead + Unpin>(mut reader: R) -> std::io::Result<String> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text).await?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::mock::{serve, MockResponse};
+
+    #[tokio::test]
+    async fn test_read_text_from_reader() {
+        let input: &[u8] = b"https://a.example.com/sub\nhttps://b.example.com/sub\n";
+        let text = read_text_from(input).await.unwrap();
+        assert_eq!(text, "https://a.example.com/sub\nhttps://b.example.com/sub\n");
+    }
+
+    #[tokio::test]
+    async fn test_gather_text_remote_url() {
+        let addr = serve(|_| MockResponse::new(200, "https://remote.example.com/sub")).await;
+        
+        let text = gather_text(&[format!("http://{}/list.txt", addr)], &Client::new(), Duration::from_secs(2), None).await.unwrap();
+        assert_eq!(text, "https://remote.example.com/sub");
+    }
+
+    #[tokio::test]
+    async fn test_gather_text_unreachable_url_is_a_network_error() {
+        // grab a free port, then close it again so nothing answers there
+        let addr = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        
+        let error = gather_text(&[format!("http://{}/list.txt", addr)], &Client::new(), Duration::from_secs(2), None).await.unwrap_err();
+        assert!(matches!(error, ProxyYoinkerError::Network(_)), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn test_gather_text_gzip_file() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+        
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("input.txt.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"https://gz.example.com/sub\nvless://id@node.example.com:443").unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+        
+        let text = gather_text(&[path.to_str().unwrap().to_string()], &Client::new(), Duration::from_secs(2), None).await.unwrap();
+        assert_eq!(text, "https://gz.example.com/sub\nvless://id@node.example.com:443");
+    }
+
+    #[tokio::test]
+    async fn test_gather_text_zip_skips_binary_entries() {
+        use std::io::Write;
+        use zip::write::FileOptions;
+        
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("input.zip");
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("links.txt", FileOptions::default()).unwrap();
+        writer.write_all(b"https://zip.example.com/sub").unwrap();
+        writer.start_file("logo.png", FileOptions::default()).unwrap();
+        writer.write_all(&[0x89, 0x50, 0xff, 0xfe, 0x00]).unwrap();
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+        
+        let text = gather_text(&[path.to_str().unwrap().to_string()], &Client::new(), Duration::from_secs(2), None).await.unwrap();
+        assert_eq!(text, "https://zip.example.com/sub");
+    }
+
+    #[tokio::test]
+    async fn test_gather_text_strips_utf8_bom() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("bom.txt");
+        std::fs::write(&path, b"\xEF\xBB\xBFhttps://bom.example.com/sub").unwrap();
+        
+        let text = gather_text(&[path.to_str().unwrap().to_string()], &Client::new(), Duration::from_secs(2), None).await.unwrap();
+        assert_eq!(text, "https://bom.example.com/sub");
+    }
+
+    #[tokio::test]
+    async fn test_gather_text_utf16_file() {
+        // what Windows Notepad writes for "Unicode"
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("utf16.txt");
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("https://utf16.example.com/sub".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        std::fs::write(&path, bytes).unwrap();
+        
+        let text = gather_text(&[path.to_str().unwrap().to_string()], &Client::new(), Duration::from_secs(2), None).await.unwrap();
+        assert_eq!(text, "https://utf16.example.com/sub");
+    }
+
+    #[test]
+    fn test_decode_text_lossy_fallback() {
+        assert_eq!(decode_text(b"plain"), ("plain".to_string(), false));
+        // Latin-1 "café" isn't valid UTF-8
+        assert_eq!(decode_text(b"caf\xE9 https://a.example.com"), ("caf\u{FFFD} https://a.example.com".to_string(), true));
+    }
+
+    #[tokio::test]
+    async fn test_gather_text_glob_filters_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("a.txt"), "https://txt.example.com/sub").unwrap();
+        std::fs::write(dir.join("b.yaml"), "https://yaml.example.com/sub").unwrap();
+        std::fs::write(dir.join("notes.md"), "https://md.example.com/sub").unwrap();
+        std::fs::write(dir.join("blob.bin"), [0xff, 0xfe, 0x00]).unwrap();
+        
+        let client = Client::new();
+        let filtered = gather_text(&[dir.to_str().unwrap().to_string()], &client, Duration::from_secs(2), Some("*.txt, *.yaml")).await.unwrap();
+        let all = gather_text(&[dir.to_str().unwrap().to_string()], &client, Duration::from_secs(2), None).await.unwrap();
+        let bad = gather_text(&[dir.to_str().unwrap().to_string()], &client, Duration::from_secs(2), Some("[")).await;
+        
+        let mut lines: Vec<&str> = filtered.lines().collect();
+        lines.sort();
+        assert_eq!(lines, vec!["https://txt.example.com/sub", "https://yaml.example.com/sub"]);
+        assert!(all.contains("md.example.com"));
+        assert!(bad.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_gather_text_multiple_inputs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let first = tmp.path().join("multi_a.txt");
+        let second = tmp.path().join("multi_b.txt");
+        std::fs::write(&first, "https://first.example.com/sub").unwrap();
+        std::fs::write(&second, "https://second.example.com/sub").unwrap();
+        
+        let paths = [first.to_str().unwrap().to_string(), second.to_str().unwrap().to_string()];
+        let text = gather_text(&paths, &Client::new(), Duration::from_secs(2), None).await.unwrap();
+        
+        assert_eq!(text, "https://first.example.com/sub\nhttps://second.example.com/sub");
+    }
+
+    #[tokio::test]
+    async fn test_load_nodes_file() {
+        use base64::Engine;
+        let vmess = base64::engine::general_purpose::STANDARD.encode(r#"{"add":"vm.example.com","port":443,"id":"x"}"#);
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("nodes_file.txt");
+        std::fs::write(&path, format!(
+            "# my nodes\n1.2.3.4:1080\n\n[2001:db8::1]:443\nvmess://{}\nnot a node\n1.2.3.4:1080\n", vmess,
+        )).unwrap();
+        let path = path.to_str().unwrap();
+        
+        let nodes = load_nodes_file(path, &RegexPatterns::new(), &[]).await.unwrap();
+        
+        assert_eq!(nodes, vec![
+            Node::new("1.2.3.4".to_string(), 1080),
+            Node::new("2001:db8::1".to_string(), 443),
+            Node::new("vm.example.com".to_string(), 443),
+        ]);
+        assert_eq!(nodes[2].protocol.as_deref(), Some("vmess"));
+        assert_eq!(nodes[0].source.as_deref(), Some(path));
+        assert!(load_nodes_file("/nonexistent/nodes.txt", &RegexPatterns::new(), &[]).await.is_err());
+    }
+}
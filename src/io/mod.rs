@@ -1,7 +1,15 @@
 pub mod input;
 pub mod output;
+pub mod stream;
+pub mod append;
+pub mod cache;
+pub mod compress;
 
 pub use input::*;
 pub use output::*;
+pub use stream::*;
+pub use append::*;
+pub use cache::*;
+pub use compress::*;
 
 // Common I/O utilities go here.. maybe
@@ -1,38 +1,659 @@
-use tokio::fs;
-use crate::models::NodeResult;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use crate::config::{RowTemplate, SortKey, Tiers};
+use crate::error::ProxyYoinkerError;
+use crate::io::compress::save_report;
+use crate::models::{Node, NodeResult, RunSummary, UrlResult};
+use crate::utils::format_duration;
 
-pub async fn write_url_report(path: &str, working_urls: &[(String, f64)]) -> Result<(), Box<dyn std::error::Error>> {
-    let mut content = String::from("# Working Subscription URLs\n\n| URL | Latency (ms) |\n|:----|------------:|\n");
+#[derive(Serialize)]
+struct ClashConfig {
+    proxies: Vec<ClashProxy>,
+}
+
+#[derive(Serialize)]
+struct ClashProxy {
+    name: String,
+    #[serde(rename = "type")]
+    proxy_type: String,
+    server: String,
+    port: u16,
+}
+
+pub async fn write_url_report(path: &str, working_urls: &[UrlResult], show_ttfb: bool) -> Result<(), ProxyYoinkerError> {
+    let mut content = if show_ttfb {
+        String::from("# Working Subscription URLs\n\n| URL | TTFB (ms) | Latency (ms) |\n|:----|---------:|------------:|\n")
+    } else {
+        String::from("# Working Subscription URLs\n\n| URL | Latency (ms) |\n|:----|------------:|\n")
+    };
     
-    let mut sorted_urls = working_urls.to_vec();
-    sorted_urls.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+    let mut sorted_urls = dedup_url_results(working_urls);
+    sorted_urls.sort_by(|a, b| {
+        a.latency.unwrap_or(0.0).partial_cmp(&b.latency.unwrap_or(0.0)).unwrap_or(std::cmp::Ordering::Equal)
+    });
     
-    for (url, latency) in sorted_urls {
-        content.push_str(&format!("| {} | {:.1} |\n", url, latency));
+    for result in sorted_urls {
+        let latency = result.latency.unwrap_or(0.0);
+        if show_ttfb {
+            let ttfb = result.ttfb.map_or("—".to_string(), |t| format!("{:.1}", t));
+            content.push_str(&format!("| {} | {} | {:.1} |\n", result.url, ttfb, latency));
+        } else {
+            content.push_str(&format!("| {} | {:.1} |\n", result.url, latency));
+        }
     }
     
-    fs::write(path, content).await?;
+    save_report(path, content).await?;
     Ok(())
 }
 
-pub async fn write_node_report(path: &str, node_results: &[NodeResult]) -> Result<(), Box<dyn std::error::Error>> {
-    let mut content = String::from("# Node URL Latencies\n\n| Host | Port | Status | Latency (ms) |\n|:-----|-----:|------:|------------:|\n");
+/// Optional columns of the Markdown node report, on top of host, port, status and latency
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReportColumns {
+    pub ttfb: bool,
+    /// `--no-node-test` inventories list each node's protocol
+    pub protocol: bool,
+    /// `--geoip`
+    pub country: bool,
+    /// `--speed-test`
+    pub speed: bool,
+    /// `--show-tier`
+    pub tier: bool,
+}
+
+pub fn node_report_header(columns: ReportColumns) -> String {
+    let mut names = String::from("| Host | Port |");
+    let mut align = String::from("|:-----|-----:|");
+    if columns.protocol {
+        names.push_str(" Protocol |");
+        align.push_str(":---------|");
+    }
+    if columns.country {
+        names.push_str(" Country |");
+        align.push_str(":--------|");
+    }
+    names.push_str(" Status |");
+    align.push_str("------:|");
+    if columns.ttfb {
+        names.push_str(" TTFB (ms) |");
+        align.push_str("---------:|");
+    }
+    names.push_str(" Latency (ms) |");
+    align.push_str("------------:|");
+    if columns.speed {
+        names.push_str(" Speed (MB/s) |");
+        align.push_str("------------:|");
+    }
+    if columns.tier {
+        names.push_str(" Tier |");
+        align.push_str(":-----|");
+    }
+    format!("# Node URL Latencies\n\n{}\n{}\n", names, align)
+}
+
+pub fn node_report_row(result: &NodeResult, columns: ReportColumns, tiers: &Tiers) -> String {
+    let mut cells = vec![result.node.host.clone(), result.node.port.to_string()];
+    if columns.protocol {
+        cells.push(result.node.protocol.clone().unwrap_or_else(|| "—".to_string()));
+    }
+    if columns.country {
+        cells.push(result.location().unwrap_or_else(|| "—".to_string()));
+    }
+    cells.push(result.status_label());
+    if columns.ttfb {
+        cells.push(result.ttfb.map_or("—".to_string(), |t| format!("{:.1}", t)));
+    }
+    cells.push(result.latency.map_or("—".to_string(), |l| format!("{:.1}", l)));
+    if columns.speed {
+        cells.push(result.throughput.map_or("—".to_string(), |t| format!("{:.2}", t)));
+    }
+    if columns.tier {
+        cells.push(result.tier(tiers).to_string());
+    }
+    format!("| {} |\n", cells.join(" | "))
+}
+
+/// `--row-template` header: the template with column titles filled in, plus a
+/// separator line when it looks like a Markdown table row
+fn template_header(template: &RowTemplate) -> String {
+    let names = template.render(|name| match name {
+        "ttfb" => "TTFB (ms)".to_string(),
+        "latency" => "Latency (ms)".to_string(),
+        "speed" => "Speed (MB/s)".to_string(),
+        other => other[..1].to_uppercase() + &other[1..],
+    });
+    let mut header = format!("# Node URL Latencies\n\n{}\n", names);
+    let trimmed = names.trim();
+    if trimmed.starts_with('|') && trimmed.ends_with('|') && trimmed.len() > 1 {
+        let columns = trimmed.matches('|').count() - 1;
+        header.push_str(&format!("|{}\n", "---|".repeat(columns)));
+    }
+    header
+}
+
+fn template_row(template: &RowTemplate, result: &NodeResult, tiers: &Tiers) -> String {
+    let row = template.render(|name| match name {
+        "host" => result.node.host.clone(),
+        "port" => result.node.port.to_string(),
+        "protocol" => result.node.protocol.clone().unwrap_or_else(|| "—".to_string()),
+        "name" => result.node.name.clone().unwrap_or_else(|| "—".to_string()),
+        "country" => result.location().unwrap_or_else(|| "—".to_string()),
+        "status" => result.status_label(),
+        "ttfb" => result.ttfb.map_or("—".to_string(), |t| format!("{:.1}", t)),
+        "latency" => result.latency.map_or("—".to_string(), |l| format!("{:.1}", l)),
+        "speed" => result.throughput.map_or("—".to_string(), |t| format!("{:.2}", t)),
+        _ => result.tier(tiers).to_string(),
+    });
+    format!("{}\n", row)
+}
+
+/// One row per URL, keeping the fastest answer (the same URL can come from several inputs).
+/// First-seen order is kept and the earlier result wins a tie.
+fn dedup_url_results(results: &[UrlResult]) -> Vec<UrlResult> {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut best: Vec<&UrlResult> = Vec::new();
+    for result in results {
+        match index.get(result.url.as_str()) {
+            Some(&i) if none_last(result.latency, best[i].latency) == Ordering::Less => best[i] = result,
+            Some(_) => {}
+            None => {
+                index.insert(&result.url, best.len());
+                best.push(result);
+            }
+        }
+    }
+    best.into_iter().cloned().collect()
+}
+
+/// One row per host:port, a working result beats a failed one, then the lower latency wins.
+/// First-seen order is kept and the earlier result wins a tie.
+pub fn dedup_node_results(results: &[NodeResult]) -> Vec<NodeResult> {
+    let mut index: HashMap<&Node, usize> = HashMap::new();
+    let mut best: Vec<&NodeResult> = Vec::new();
+    for result in results {
+        match index.get(&result.node) {
+            Some(&i) => {
+                let better = result.is_working().cmp(&best[i].is_working()).reverse().then(none_last(result.latency, best[i].latency));
+                if better == Ordering::Less {
+                    best[i] = result;
+                }
+            }
+            None => {
+                index.insert(&result.node, best.len());
+                best.push(result);
+            }
+        }
+    }
+    best.into_iter().cloned().collect()
+}
+
+/// The CSV country columns only show up once `--geoip` found something
+fn has_country(node_results: &[NodeResult]) -> bool {
+    node_results.iter().any(|r| r.country.is_some() || r.city.is_some())
+}
+
+/// `None` sorts after every `Some`
+fn none_last<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Orders by `key`, ties fall back to host then port
+pub fn sort_node_results(node_results: &mut [NodeResult], key: SortKey) {
+    node_results.sort_by(|a, b| {
+        let by_host = a.node.host.cmp(&b.node.host).then_with(|| a.node.port.cmp(&b.node.port));
+        match key {
+            SortKey::Host => by_host,
+            SortKey::Port => a.node.port.cmp(&b.node.port).then_with(|| a.node.host.cmp(&b.node.host)),
+            SortKey::Latency => none_last(a.latency, b.latency).then(by_host),
+            SortKey::Status => none_last(a.status, b.status).then(by_host),
+        }
+    });
+}
+
+/// `template` (`--row-template`) replaces the default columns when given
+pub async fn write_node_report(path: &str, node_results: &[NodeResult], columns: ReportColumns, sort: SortKey, tiers: &Tiers, template: Option<&RowTemplate>) -> Result<(), ProxyYoinkerError> {
+    let mut content = match template {
+        Some(template) => template_header(template),
+        None => node_report_header(columns),
+    };
+    
+    let mut sorted_results = dedup_node_results(node_results);
+    sort_node_results(&mut sorted_results, sort);
     
-    let mut sorted_results = node_results.to_vec();
-    sorted_results.sort_by(|a, b| {
-        a.node.host.cmp(&b.node.host)
-            .then_with(|| a.node.port.cmp(&b.node.port))
+    for result in &sorted_results {
+        content.push_str(&match template {
+            Some(template) => template_row(template, result, tiers),
+            None => node_report_row(result, columns, tiers),
+        });
+    }
+    
+    save_report(path, content).await?;
+    Ok(())
+}
+
+pub async fn write_node_report_csv(path: &str, node_results: &[NodeResult]) -> Result<(), ProxyYoinkerError> {
+    let show_country = has_country(node_results);
+    let mut content = String::from(if show_country {
+        "host,port,status,latency_ms,country,city\n"
+    } else {
+        "host,port,status,latency_ms\n"
     });
     
-    for result in sorted_results {
-        let status = result.status.map_or("—".to_string(), |s| s.to_string());
-        let latency = result.latency.map_or("—".to_string(), |l| format!("{:.1}", l));
+    for result in node_results {
+        let status = result.status.map_or(String::new(), |s| s.to_string());
+        let latency = result.latency.map_or(String::new(), |l| format!("{:.1}", l));
         content.push_str(&format!(
-            "| {} | {} | {} | {} |\n",
-            result.node.host, result.node.port, status, latency
+            "{},{},{},{}",
+            csv_field(&result.node.host), result.node.port, status, latency
         ));
+        if show_country {
+            content.push_str(&format!(
+                ",{},{}",
+                csv_field(result.country.as_deref().unwrap_or("")),
+                csv_field(result.city.as_deref().unwrap_or(""))
+            ));
+        }
+        content.push('\n');
+    }
+    
+    save_report(path, content).await?;
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
     }
+}
+
+/// `--diagnostics`: one line per subscription, see `SubscriptionParse::diagnostic_line`
+pub async fn write_diagnostics(path: &str, lines: &[String]) -> Result<(), ProxyYoinkerError> {
+    let mut content = lines.join("\n");
+    content.push('\n');
+    save_report(path, content).await?;
+    Ok(())
+}
+
+pub async fn write_summary(path: &str, summary: &RunSummary) -> Result<(), ProxyYoinkerError> {
+    let latency = |l: Option<f64>| l.map_or("—".to_string(), |l| format!("{:.1} ms", l));
+    let content = format!(
+        "# Run Summary\n\n| Metric | Value |\n|:-------|------:|\n\
+         | Total URLs | {} |\n\
+         | Working URLs | {} ({:.1}%) |\n\
+         | Total nodes | {} |\n\
+         | Reachable nodes | {} ({:.1}%) |\n\
+         | Median latency | {} |\n\
+         | p95 latency | {} |\n\
+         | Duration | {} |\n",
+        summary.total_urls,
+        summary.working_urls, summary.url_success_pct(),
+        summary.total_nodes,
+        summary.reachable_nodes, summary.node_success_pct(),
+        latency(summary.median_latency),
+        latency(summary.p95_latency),
+        format_duration(summary.duration_secs),
+    );
     
-    fs::write(path, content).await?;
+    save_report(path, content).await?;
     Ok(())
 }
+
+/// Whether `result` is a working node that answered within `max_latency` ms
+pub fn within_max_latency(result: &NodeResult, max_latency: f64) -> bool {
+    result.is_working() && result.latency.is_some_and(|l| l <= max_latency)
+}
+
+/// Keeps only nodes that answered within `max_latency` ms, failures are dropped
+pub fn filter_by_max_latency(node_results: &[NodeResult], max_latency: f64) -> Vec<NodeResult> {
+    node_results
+        .iter()
+        .filter(|r| within_max_latency(r, max_latency))
+        .cloned()
+        .collect()
+}
+
+/// `--only-working`: drops every node whose check failed, whatever its latency
+pub fn filter_working(node_results: &[NodeResult]) -> Vec<NodeResult> {
+    node_results.iter().filter(|r| r.is_working()).cloned().collect()
+}
+
+pub async fn write_node_report_json(path: &str, node_results: &[NodeResult]) -> Result<(), ProxyYoinkerError> {
+    let content = serde_json::to_string_pretty(node_results)?;
+    save_report(path, content).await?;
+    Ok(())
+}
+
+pub async fn write_clash_yaml(path: &str, node_results: &[NodeResult]) -> Result<(), ProxyYoinkerError> {
+    let proxies = node_results
+        .iter()
+        .filter(|r| r.is_working())
+        .map(|r| ClashProxy {
+            name: format!("{}:{}", r.node.host, r.node.port),
+            proxy_type: "http".to_string(),
+            server: r.node.host.clone(),
+            port: r.node.port,
+        })
+        .collect();
+    
+    let content = serde_yaml::to_string(&ClashConfig { proxies })?;
+    save_report(path, content).await?;
+    Ok(())
+}
+
+/// Original links of the working nodes, one per line. Nodes that came from
+/// config files (Clash, V2Ray JSON, ...) have no link and are skipped.
+pub async fn write_raw_uris(path: &str, node_results: &[NodeResult]) -> Result<(), ProxyYoinkerError> {
+    let mut content = String::new();
+    for uri in node_results.iter().filter(|r| r.is_working()).filter_map(|r| r.node.raw_uri.as_deref()) {
+        content.push_str(uri);
+        content.push('\n');
+    }
+    save_report(path, content).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tokio::fs;
+
+    fn sorted_hosts(key: SortKey) -> Vec<String> {
+        let mut results = vec![
+            NodeResult::new(Node::new("c.example.com".to_string(), 80), Some(0), Some(50.0)),
+            NodeResult::new(Node::new("a.example.com".to_string(), 443), None, None),
+            NodeResult::new(Node::new("b.example.com".to_string(), 8080), Some(200), Some(20.0)),
+            NodeResult::new(Node::new("d.example.com".to_string(), 443), Some(0), Some(90.0)),
+        ];
+        sort_node_results(&mut results, key);
+        results.into_iter().map(|r| r.node.host).collect()
+    }
+
+    #[test]
+    fn test_sort_node_results() {
+        assert_eq!(sorted_hosts(SortKey::Host), vec!["a.example.com", "b.example.com", "c.example.com", "d.example.com"]);
+        assert_eq!(sorted_hosts(SortKey::Port), vec!["c.example.com", "a.example.com", "d.example.com", "b.example.com"]);
+        assert_eq!(sorted_hosts(SortKey::Latency), vec!["b.example.com", "c.example.com", "d.example.com", "a.example.com"]);
+        assert_eq!(sorted_hosts(SortKey::Status), vec!["c.example.com", "d.example.com", "b.example.com", "a.example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_node_report_row_template() {
+        let template: RowTemplate = "| {name} | {host}:{port} | {latency} | {tier} |".parse().unwrap();
+        let mut node = Node::new("fast.example.com".to_string(), 443);
+        node.name = Some("Tokyo 01".to_string());
+        let results = [
+            NodeResult::new(node, Some(200), Some(42.0)),
+            NodeResult::new(Node::new("slow.example.com".to_string(), 80), None, None),
+        ];
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("row_template.md");
+        let path = path.to_str().unwrap();
+        
+        write_node_report(path, &results, ReportColumns::default(), SortKey::Host, &Tiers::default(), Some(&template)).await.unwrap();
+        let content = fs::read_to_string(path).await.unwrap();
+        
+        assert_eq!(content, "# Node URL Latencies\n\n\
+                             | Name | Host:Port | Latency (ms) | Tier |\n\
+                             |---|---|---|---|\n\
+                             | Tokyo 01 | fast.example.com:443 | 42.0 | fast |\n\
+                             | — | slow.example.com:80 | — | — |\n");
+    }
+
+    #[tokio::test]
+    async fn test_reports_dedup_keep_fastest() {
+        let url = |latency: f64| UrlResult { url: "https://dup.example.com/sub".to_string(), status: Some(200), latency: Some(latency), ttfb: None, failure: None };
+        let node = |status: Option<u16>, latency: Option<f64>| NodeResult::new(Node::new("dup.example.com".to_string(), 443), status, latency);
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let url_path = dir.join("dedup_urls.md");
+        let node_path = dir.join("dedup_nodes.md");
+        let (url_path, node_path) = (url_path.to_str().unwrap(), node_path.to_str().unwrap());
+        
+        write_url_report(url_path, &[url(80.0), url(25.0), url(60.0)], false).await.unwrap();
+        let nodes = [node(Some(0), Some(90.0)), node(None, None), node(Some(0), Some(30.0))];
+        write_node_report(node_path, &nodes, ReportColumns::default(), SortKey::Host, &Tiers::default(), None).await.unwrap();
+        let urls = fs::read_to_string(url_path).await.unwrap();
+        let nodes = fs::read_to_string(node_path).await.unwrap();
+        
+        let url_rows: Vec<&str> = urls.lines().filter(|l| l.contains("dup.example.com")).collect();
+        assert_eq!(url_rows, vec!["| https://dup.example.com/sub | 25.0 |"]);
+        let node_rows: Vec<&str> = nodes.lines().filter(|l| l.contains("dup.example.com")).collect();
+        assert_eq!(node_rows, vec!["| dup.example.com | 443 | 0 | 30.0 |"]);
+    }
+
+    #[test]
+    fn test_dedup_node_results_first_wins_a_tie() {
+        let node = |name: &str, host: &str| NodeResult::new(Node::new(host.to_string(), 443).with_name(name), Some(0), Some(40.0));
+        let results = [node("first", "b.example.com"), node("other", "a.example.com"), node("second", "b.example.com")];
+        for _ in 0..10 {
+            let deduped = dedup_node_results(&results);
+            let names: Vec<_> = deduped.iter().map(|r| r.node.name.as_deref().unwrap()).collect();
+            assert_eq!(names, vec!["first", "other"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_node_report_country_column() {
+        let mut located = NodeResult::new(Node::new("a.example.com".to_string(), 443), Some(0), Some(12.5));
+        located.country = Some("US".to_string());
+        located.city = Some("Mountain View".to_string());
+        let results = vec![located, NodeResult::new(Node::new("b.example.com".to_string(), 443), None, None)];
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("node_report_country.md");
+        let path = path.to_str().unwrap();
+        
+        write_node_report(path, &results, ReportColumns { country: true, ..ReportColumns::default() }, SortKey::Host, &Tiers::default(), None).await.unwrap();
+        let content = fs::read_to_string(path).await.unwrap();
+        
+        assert!(content.contains("| Host | Port | Country | Status | Latency (ms) |\n"));
+        assert!(content.contains("| a.example.com | 443 | US (Mountain View) | 0 | 12.5 |\n"));
+        assert!(content.contains("| b.example.com | 443 | — | — | — |\n"));
+    }
+
+    #[test]
+    fn test_node_report_row_failure_label() {
+        use crate::models::FailureKind;
+        
+        let timed_out = NodeResult::new(Node::new("a.example.com".to_string(), 443), None, None).with_failure(FailureKind::Timeout);
+        assert_eq!(node_report_row(&timed_out, ReportColumns::default(), &Tiers::default()), "| a.example.com | 443 | TIMEOUT | — |\n");
+        let tiered = ReportColumns { tier: true, ..ReportColumns::default() };
+        assert_eq!(node_report_row(&timed_out, tiered, &Tiers::default()), "| a.example.com | 443 | TIMEOUT | — | dead |\n");
+        let untested = NodeResult::new(Node::new("a.example.com".to_string(), 443), None, None);
+        assert_eq!(node_report_row(&untested, tiered, &Tiers::default()), "| a.example.com | 443 | — | — | — |\n");
+        
+        let mut listed = NodeResult::new(Node::new("a.example.com".to_string(), 443), None, None);
+        listed.node.protocol = Some("vless".to_string());
+        let inventory = ReportColumns { protocol: true, ..ReportColumns::default() };
+        assert_eq!(node_report_row(&listed, inventory, &Tiers::default()), "| a.example.com | 443 | vless | — | — |\n");
+    }
+
+    #[test]
+    fn test_tier_boundaries() {
+        let tier = |latency: f64, tiers: &Tiers| NodeResult::new(Node::new("a.example.com".to_string(), 443), Some(0), Some(latency)).tier(tiers);
+        let defaults = Tiers::default();
+        let cases = [(0.0, "fast"), (99.9, "fast"), (100.0, "ok"), (499.9, "ok"), (500.0, "slow"), (1999.9, "slow"), (2000.0, "dead")];
+        for (latency, expected) in cases {
+            assert_eq!(tier(latency, &defaults), expected, "{} ms", latency);
+        }
+        
+        let custom: Tiers = "50, 150,300".parse().unwrap();
+        assert_eq!((tier(60.0, &custom), tier(150.0, &custom), tier(300.0, &custom)), ("ok", "slow", "dead"));
+        let refused = NodeResult::new(Node::new("a.example.com".to_string(), 443), Some(503), Some(5.0));
+        assert_eq!(refused.tier(&defaults), "dead");
+        for bad in ["100,500", "500,100,2000", "fast,ok,slow", "0,1,2"] {
+            assert!(bad.parse::<Tiers>().is_err(), "{}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raw_uris_survive_parsing() {
+        use crate::models::RegexPatterns;
+        use crate::parsers::detect_format_and_parse;
+        
+        let text = "vless://uuid@up.example.com:443?security=tls&sni=x.example.com#Up%20Node\nvless://uuid@down.example.com:443#down";
+        let (nodes, _) = detect_format_and_parse(text, &RegexPatterns::new(), &[], None);
+        let results: Vec<NodeResult> = nodes.into_iter().map(|node| {
+            let status = (node.host == "up.example.com").then_some(0);
+            NodeResult::new(node, status, Some(10.0))
+        }).collect();
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("raw_out.txt");
+        let path = path.to_str().unwrap();
+        
+        write_raw_uris(path, &results).await.unwrap();
+        let content = fs::read_to_string(path).await.unwrap();
+        
+        assert_eq!(content, "vless://uuid@up.example.com:443?security=tls&sni=x.example.com#Up%20Node\n");
+    }
+
+    #[tokio::test]
+    async fn test_write_node_report_json_roundtrip() {
+        let results = vec![
+            NodeResult::new(Node::new("a.example.com".to_string(), 443), Some(0), Some(12.5)),
+            NodeResult::new(Node::new("10.0.0.1".to_string(), 8080), None, None),
+        ];
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("node_report.json");
+        let path = path.to_str().unwrap();
+
+        write_node_report_json(path, &results).await.unwrap();
+        let parsed: Value = serde_json::from_str(&fs::read_to_string(path).await.unwrap()).unwrap();
+
+        let arr = parsed.as_array().unwrap();
+        assert_eq!(arr.len(), 2);
+        assert_eq!(arr[0]["host"], "a.example.com");
+        assert_eq!(arr[0]["port"], 443);
+        assert_eq!(arr[0]["status"], 0);
+        assert_eq!(arr[0]["latency_ms"], 12.5);
+        assert_eq!(arr[1]["host"], "10.0.0.1");
+        assert!(arr[1]["status"].is_null());
+        assert!(arr[1]["latency_ms"].is_null());
+    }
+
+    /// Splits one CSV line, honouring quotes (just enough for the test)
+    fn split_csv_line(line: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => { chars.next(); fields.last_mut().unwrap().push('"'); }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                _ => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    #[tokio::test]
+    async fn test_write_node_report_csv() {
+        let mut located = NodeResult::new(Node::new("odd,\"host\"".to_string(), 80), None, None);
+        located.country = Some("X,Y".to_string());
+        let results = vec![NodeResult::new(Node::new("a.example.com".to_string(), 443), Some(0), Some(12.5)), located];
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("node_report.csv");
+        let path = path.to_str().unwrap();
+
+        write_node_report_csv(path, &results).await.unwrap();
+        let content = fs::read_to_string(path).await.unwrap();
+
+        let rows: Vec<Vec<String>> = content.lines().map(split_csv_line).collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|r| r.len() == 6));
+        assert_eq!(rows[0], vec!["host", "port", "status", "latency_ms", "country", "city"]);
+        assert_eq!(rows[1], vec!["a.example.com", "443", "0", "12.5", "", ""]);
+        assert_eq!(rows[2], vec!["odd,\"host\"", "80", "", "", "X,Y", ""]);
+    }
+
+    #[tokio::test]
+    async fn test_write_summary() {
+        let mut results: Vec<NodeResult> = (1..=20)
+            .map(|i| NodeResult::new(Node::new(format!("n{}", i), 443), Some(0), Some(i as f64 * 10.0)))
+            .collect();
+        results.push(NodeResult::new(Node::new("dead1".to_string(), 443), None, None));
+        results.push(NodeResult::new(Node::new("dead2".to_string(), 443), None, None));
+        
+        let summary = RunSummary::new(8, 2, &results, 75.0);
+        assert_eq!(summary.reachable_nodes, 20);
+        assert_eq!(summary.total_nodes, 22);
+        assert_eq!(summary.url_success_pct(), 25.0);
+        assert!((summary.node_success_pct() - 90.909).abs() < 0.01);
+        assert_eq!(summary.median_latency, Some(100.0));
+        assert_eq!(summary.p95_latency, Some(190.0));
+        
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("summary.md");
+        let path = path.to_str().unwrap();
+        write_summary(path, &summary).await.unwrap();
+        let content = fs::read_to_string(path).await.unwrap();
+        assert!(content.contains("| Working URLs | 2 (25.0%) |"));
+        assert!(content.contains("| Reachable nodes | 20 (90.9%) |"));
+        assert!(content.contains("| p95 latency | 190.0 ms |"));
+    }
+
+    #[test]
+    fn test_filter_by_max_latency() {
+        let results = vec![
+            NodeResult::new(Node::new("fast".to_string(), 1), Some(0), Some(50.0)),
+            NodeResult::new(Node::new("edge".to_string(), 2), Some(0), Some(200.0)),
+            NodeResult::new(Node::new("slow".to_string(), 3), Some(0), Some(900.0)),
+            NodeResult::new(Node::new("dead".to_string(), 4), None, None),
+            NodeResult::new(Node::new("forbidden".to_string(), 5), Some(403), Some(40.0)),
+        ];
+        let hosts: Vec<String> = filter_by_max_latency(&results, 200.0)
+            .into_iter()
+            .map(|r| r.node.host)
+            .collect();
+        assert_eq!(hosts, vec!["fast", "edge"]);
+    }
+
+    #[tokio::test]
+    async fn test_node_report_only_working() {
+        let results = vec![
+            NodeResult::new(Node::new("up.example.com".to_string(), 443), Some(200), Some(40.0)),
+            NodeResult::new(Node::new("down.example.com".to_string(), 443), None, None),
+            NodeResult::new(Node::new("denied.example.com".to_string(), 443), Some(403), Some(30.0)),
+        ];
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("only_working.md");
+        let path = path.to_str().unwrap();
+        
+        write_node_report(path, &filter_working(&results), ReportColumns::default(), SortKey::Host, &Tiers::default(), None).await.unwrap();
+        let content = fs::read_to_string(path).await.unwrap();
+        
+        assert!(content.contains("up.example.com"));
+        assert!(!content.contains("down.example.com"));
+        assert!(!content.contains("denied.example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_write_clash_yaml_only_working() {
+        let results = vec![
+            NodeResult::new(Node::new("a.example.com".to_string(), 443), Some(0), Some(12.5)),
+            NodeResult::new(Node::new("b.example.com".to_string(), 80), None, None),
+            NodeResult::new(Node::new("c.example.com".to_string(), 8080), Some(200), Some(80.0)),
+            NodeResult::new(Node::new("d.example.com".to_string(), 443), Some(503), Some(9.0)),
+        ];
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("clash.yaml");
+        let path = path.to_str().unwrap();
+
+        write_clash_yaml(path, &results).await.unwrap();
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&fs::read_to_string(path).await.unwrap()).unwrap();
+
+        let proxies = parsed["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[0]["server"].as_str(), Some("a.example.com"));
+        assert_eq!(proxies[0]["type"].as_str(), Some("http"));
+        assert_eq!(proxies[1]["port"].as_u64(), Some(8080));
+    }
+}
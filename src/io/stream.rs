@@ -0,0 +1,115 @@
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task::JoinHandle;
+use crate::config::Tiers;
+use crate::models::NodeResult;
+use crate::io::compress::create_report;
+use crate::io::output::{node_report_header, node_report_row, ReportColumns};
+
+type StreamHandle = (UnboundedSender<NodeResult>, JoinHandle<std::io::Result<()>>);
+
+/// Opens the node report up front and appends a row for every result sent down the
+/// channel, so a crash mid-run still leaves everything finished so far on disk.
+/// Rows land in completion order (no sorting). Drop the sender, then await the handle.
+pub async fn spawn_node_report_stream(path: &str, columns: ReportColumns, tiers: Tiers) -> std::io::Result<StreamHandle> {
+    let mut file = create_report(path).await?;
+    file.write_all(node_report_header(columns).as_bytes()).await?;
+    file.flush().await?;
+    
+    Ok(spawn_line_writer(file, move |result| Ok(node_report_row(result, columns, &tiers))))
+}
+
+/// `--ndjson`: one JSON object per line per result, in completion order
+pub async fn spawn_ndjson_stream(path: &str) -> std::io::Result<StreamHandle> {
+    let file = create_report(path).await?;
+    Ok(spawn_line_writer(file, |result| {
+        let mut line = serde_json::to_string(result)?;
+        line.push('\n');
+        Ok(line)
+    }))
+}
+
+/// The one task that owns `file`, so lines from concurrent checks never interleave
+fn spawn_line_writer<W, F>(mut file: W, render: F) -> StreamHandle
+where
+    W: AsyncWrite + Send + Unpin + 'static,
+    F: Fn(&NodeResult) -> std::io::Result<String> + Send + 'static,
+{
+    let (tx, mut rx) = unbounded_channel::<NodeResult>();
+    let handle = tokio::spawn(async move {
+        while let Some(result) = rx.recv().await {
+            file.write_all(render(&result)?.as_bytes()).await?;
+            file.flush().await?;
+        }
+        // closes the gzip stream for `--compress`
+        file.shutdown().await
+    });
+    
+    (tx, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::output::write_node_report;
+    use crate::models::Node;
+    use tokio::fs;
+
+    #[tokio::test]
+    async fn test_stream_matches_batch_writer() {
+        // already in host/port order so the batch writer's sort doesn't reorder anything
+        let mut located = NodeResult::new(Node::new("a.example.com".to_string(), 443), Some(0), Some(12.5));
+        located.country = Some("US".to_string());
+        let results = vec![
+            located,
+            NodeResult::new(Node::new("b.example.com".to_string(), 80), None, None),
+            NodeResult::new(Node::new("c.example.com".to_string(), 8080), Some(200), Some(80.0)),
+        ];
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let batch_path = dir.join("batch.md");
+        let stream_path = dir.join("stream.md");
+        let (batch_path, stream_path) = (batch_path.to_str().unwrap(), stream_path.to_str().unwrap());
+        
+        let columns = ReportColumns { ttfb: true, country: true, ..ReportColumns::default() };
+        write_node_report(batch_path, &results, columns, crate::config::SortKey::Host, &Tiers::default(), None).await.unwrap();
+        let (tx, handle) = spawn_node_report_stream(stream_path, columns, Tiers::default()).await.unwrap();
+        for result in &results {
+            tx.send(result.clone()).unwrap();
+        }
+        drop(tx);
+        handle.await.unwrap().unwrap();
+        
+        let batch = fs::read_to_string(batch_path).await.unwrap();
+        let streamed = fs::read_to_string(stream_path).await.unwrap();
+        assert_eq!(batch, streamed);
+        assert!(streamed.contains("| a.example.com | 443 | US | 0 | — | 12.5 |"));
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_lines_parse_independently() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("stream.ndjson");
+        let path = path.to_str().unwrap();
+        
+        let (tx, handle) = spawn_ndjson_stream(path).await.unwrap();
+        let senders: Vec<_> = (0..20u16).map(|i| {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let status = (i % 2 == 0).then_some(0);
+                tx.send(NodeResult::new(Node::new(format!("n{}.example.com", i), 1000 + i), status, Some(f64::from(i)))).unwrap();
+            })
+        }).collect();
+        for sender in senders {
+            sender.await.unwrap();
+        }
+        drop(tx);
+        handle.await.unwrap().unwrap();
+        
+        let content = fs::read_to_string(path).await.unwrap();
+        assert!(!content.trim_start().starts_with('['));
+        let lines: Vec<serde_json::Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 20);
+        assert!(lines.iter().all(|l| l["host"].as_str().unwrap().ends_with(".example.com")));
+    }
+}
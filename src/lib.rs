@@ -1,7 +1,21 @@
+#![allow(clippy::collapsible_if)] // nested if-lets read better for the parsers
+
 pub mod config;
+pub mod error;
 pub mod models;
 pub mod parsers;
 pub mod network;
 pub mod discovery;
 pub mod io;
 pub mod utils;
+pub mod filters;
+pub mod geoip;
+pub mod pipeline;
+pub mod shutdown;
+pub mod progress;
+pub mod eta;
+pub mod logging;
+pub mod runner;
+
+pub use error::ProxyYoinkerError;
+pub use runner::{run, run_with_shutdown, RunConfig, RunReport};
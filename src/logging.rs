@@ -0,0 +1,107 @@
+use tracing::Level;
+
+/// `--quiet` wins over everything, `--verbose` adds the per-item debug lines
+pub fn log_level(verbose: bool, quiet: bool) -> Level {
+    if quiet {
+        Level::ERROR
+    } else if verbose {
+        Level::DEBUG
+    } else {
+        Level::INFO
+    }
+}
+
+/// Installs the global stdout logger. Does nothing if one is already set
+/// (e.g. when embedding the crate and the host app brought its own).
+pub fn init_logging(verbose: bool, quiet: bool) {
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(log_level(verbose, quiet))
+        .with_target(false)
+        .without_time()
+        .try_init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Captured {
+        type Writer = Captured;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn capture(level: Level, emit: impl FnOnce()) -> String {
+        let out = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(out.clone())
+            .with_ansi(false)
+            .without_time()
+            .finish();
+        tracing::subscriber::with_default(subscriber, emit);
+        let bytes = out.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_levels_filter_events() {
+        let emit = || {
+            tracing::debug!("per-item line");
+            tracing::info!("phase banner");
+            tracing::warn!("something failed");
+            tracing::error!("fatal");
+        };
+        
+        let normal = capture(log_level(false, false), emit);
+        assert!(!normal.contains("per-item line"));
+        assert!(normal.contains("phase banner") && normal.contains("something failed"));
+        
+        let verbose = capture(log_level(true, false), emit);
+        assert!(verbose.contains("per-item line"));
+        
+        let quiet = capture(log_level(false, true), emit);
+        assert!(!quiet.contains("phase banner") && !quiet.contains("something failed"));
+        assert!(quiet.contains("fatal"));
+    }
+
+    #[test]
+    fn test_failed_items_logged_at_warn() {
+        let progress = crate::progress::ProgressMode::default().bar(2, "Nodes");
+        let emit = || {
+            progress.line(false, "Node [1/2] up.example.com:443 -> 0, 12.0 ms".to_string());
+            progress.line(true, "Node [2/2] down.example.com:443 -> TIMEOUT, —".to_string());
+        };
+        
+        let normal = capture(log_level(false, false), emit);
+        assert!(normal.contains("down.example.com") && normal.contains("WARN"));
+        assert!(!normal.contains("up.example.com"));
+    }
+
+    #[test]
+    fn test_parser_attempts_logged_at_debug() {
+        let patterns = crate::models::RegexPatterns::new();
+        let body = "proxies:\n  - {name: a, type: ss, server: ss.example.com, port: 8388}\n";
+        let parse = || { crate::parsers::detect_format_and_parse(body, &patterns, &[], None); };
+        
+        assert!(capture(Level::DEBUG, parse).contains("Trying Clash YAML parser"));
+        assert!(!capture(Level::INFO, parse).contains("Trying Clash YAML parser"));
+    }
+}
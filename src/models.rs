@@ -1,19 +1,78 @@
 use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use serde::Serialize;
+use crate::config::Tiers;
+use crate::utils::{normalize_host, percent, percentile};
 
 
-#[derive(Debug, Clone)]
+/// A proxy endpoint. Only `host` and `port` take part in Hash/Eq, the rest is whatever
+/// the subscription said about it.
+#[derive(Debug, Clone, Serialize)]
 pub struct Node {
     pub host: String,
     pub port: u16,
+    /// vmess, trojan, ss, ... when the source tells us
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    /// The link exactly as it appeared in the subscription (`vless://...#name`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_uri: Option<String>,
+    /// Subscription URL the node was parsed from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Display name from the subscription (Clash `name`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Transport from the link (`ws`, `grpc`, `tcp`, ...), vmess only for now
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// Whether the link asks for TLS, when it says
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
 }
 
 impl Node {
     pub fn new(host: String, port: u16) -> Self {
-        Self { host, port }
+        Self { host: normalize_host(&host), port, protocol: None, raw_uri: None, source: None, name: None, network: None, tls: None }
+    }
+    
+    pub fn with_protocol(mut self, protocol: &str) -> Self {
+        self.protocol = Some(protocol.to_lowercase());
+        self
+    }
+    
+    pub fn with_raw_uri(mut self, uri: &str) -> Self {
+        self.raw_uri = Some(uri.to_string());
+        self
+    }
+    
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+    
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+    
+    pub fn with_transport(mut self, network: Option<String>, tls: Option<bool>) -> Self {
+        self.network = network;
+        self.tls = tls;
+        self
     }
     
     pub fn url(&self) -> String {
-        format!("http://{}:{}", self.host, self.port)
+        format!("http://{}:{}", self.authority_host(), self.port)
+    }
+    
+    /// Host as it should appear in a URL authority (IPv6 literals get their brackets back)
+    pub fn authority_host(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        }
     }
 }
 
@@ -32,42 +91,435 @@ impl PartialEq for Node {
 
 impl Eq for Node {}
 
-#[derive(Debug)]
+/// Why a check failed, shown in place of the status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureKind {
+    Timeout,
+    ConnectionRefused,
+    DnsError,
+    Tls,
+    Other,
+}
+
+impl FailureKind {
+    /// What the reports print in the status column
+    pub fn label(self) -> &'static str {
+        match self {
+            FailureKind::Timeout => "TIMEOUT",
+            FailureKind::ConnectionRefused => "REFUSED",
+            FailureKind::DnsError => "DNS",
+            FailureKind::Tls => "TLS",
+            FailureKind::Other => "FAIL",
+        }
+    }
+}
+
+impl std::fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl std::str::FromStr for FailureKind {
+    type Err = ();
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "TIMEOUT" => Ok(FailureKind::Timeout),
+            "REFUSED" => Ok(FailureKind::ConnectionRefused),
+            "DNS" => Ok(FailureKind::DnsError),
+            "TLS" => Ok(FailureKind::Tls),
+            "FAIL" => Ok(FailureKind::Other),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct UrlResult {
     pub url: String,
     pub status: Option<u16>,
     pub latency: Option<f64>,
+    /// Time until the response headers arrived, `latency` also includes the body
+    pub ttfb: Option<f64>,
+    pub failure: Option<FailureKind>,
 }
 
-#[derive(Debug, Clone)]
+impl UrlResult {
+    /// Status code, or the failure label when there's none
+    pub fn status_label(&self) -> String {
+        status_label(self.status, self.failure)
+    }
+}
+
+/// How many URL checks ended in each status label, codes in ascending order and then the
+/// failures (`TIMEOUT`, `DNS`, ...). `format` gives `200: 45, 403: 12, TIMEOUT: 30`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusHistogram(pub Vec<(String, usize)>);
+
+impl StatusHistogram {
+    pub fn collect(results: &[UrlResult]) -> Self {
+        let mut counts: HashMap<(Option<u16>, String), usize> = HashMap::new();
+        for result in results {
+            *counts.entry((result.status, result.status_label())).or_default() += 1;
+        }
+        let mut buckets: Vec<_> = counts.into_iter().collect();
+        // every Some(code) before the None failures, which then go by label
+        buckets.sort_by(|((a, a_label), _), ((b, b_label), _)| a.is_none().cmp(&b.is_none()).then(a.cmp(b)).then(a_label.cmp(b_label)));
+        Self(buckets.into_iter().map(|((_, label), count)| (label, count)).collect())
+    }
+    
+    pub fn format(&self) -> String {
+        self.0.iter().map(|(label, count)| format!("{}: {}", label, count)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct NodeResult {
+    #[serde(flatten)]
     pub node: Node,
     pub status: Option<u16>,
+    #[serde(rename = "latency_ms")]
     pub latency: Option<f64>,
+    #[serde(rename = "ttfb_ms")]
+    pub ttfb: Option<f64>,
+    /// Latency through `--upstream-proxy` and then the node
+    #[serde(rename = "chained_latency_ms", skip_serializing_if = "Option::is_none")]
+    pub chained_latency: Option<f64>,
+    /// MB/s from `--speed-test`
+    #[serde(rename = "throughput_mb_s", skip_serializing_if = "Option::is_none")]
+    pub throughput: Option<f64>,
+    /// ISO country code from `--geoip`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+}
+
+impl NodeResult {
+    pub fn new(node: Node, status: Option<u16>, latency: Option<f64>) -> Self {
+        Self { node, status, latency, ttfb: None, chained_latency: None, throughput: None, country: None, city: None, failure: None }
+    }
+    
+    /// `fast` / `ok` / `slow` by latency, `dead` for failed (or slower than `slow`), `—` if untested
+    pub fn tier(&self, tiers: &Tiers) -> &'static str {
+        match (self.is_working(), self.latency) {
+            (true, Some(l)) if l < tiers.fast => "fast",
+            (true, Some(l)) if l < tiers.ok => "ok",
+            (true, Some(l)) if l < tiers.slow => "slow",
+            (false, _) if self.failure.is_none() && self.status.is_none() => "—",
+            _ => "dead",
+        }
+    }
+    
+    /// `US (Mountain View)`, `US`, or `None` when there's no geoip data
+    pub fn location(&self) -> Option<String> {
+        match (&self.country, &self.city) {
+            (Some(country), Some(city)) => Some(format!("{} ({})", country, city)),
+            (Some(country), None) => Some(country.clone()),
+            (None, Some(city)) => Some(city.clone()),
+            (None, None) => None,
+        }
+    }
+    
+    /// A node counts as working when it answered 2xx/3xx, or a non-HTTP check (status 0) got through
+    pub fn is_working(&self) -> bool {
+        matches!(self.status, Some(0 | 200..=399))
+    }
+    
+    pub fn with_failure(mut self, failure: FailureKind) -> Self {
+        self.failure = Some(failure);
+        self
+    }
+    
+    /// Status code, or the failure label when there's none
+    pub fn status_label(&self) -> String {
+        status_label(self.status, self.failure)
+    }
+}
+
+fn status_label(status: Option<u16>, failure: Option<FailureKind>) -> String {
+    match (status, failure) {
+        (Some(status), _) => status.to_string(),
+        (None, Some(failure)) => failure.to_string(),
+        (None, None) => "—".to_string(),
+    }
+}
+
+/// Aggregate numbers for the summary report
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub total_urls: usize,
+    pub working_urls: usize,
+    pub total_nodes: usize,
+    pub reachable_nodes: usize,
+    pub median_latency: Option<f64>,
+    pub p95_latency: Option<f64>,
+    pub duration_secs: f64,
+}
+
+impl RunSummary {
+    pub fn new(total_urls: usize, working_urls: usize, node_results: &[NodeResult], duration_secs: f64) -> Self {
+        let latencies: Vec<f64> = node_results
+            .iter()
+            .filter(|r| r.is_working())
+            .filter_map(|r| r.latency)
+            .collect();
+        
+        Self {
+            total_urls,
+            working_urls,
+            total_nodes: node_results.len(),
+            reachable_nodes: node_results.iter().filter(|r| r.is_working()).count(),
+            median_latency: percentile(&latencies, 50.0),
+            p95_latency: percentile(&latencies, 95.0),
+            duration_secs,
+        }
+    }
+    
+    pub fn url_success_pct(&self) -> f64 {
+        percent(self.working_urls, self.total_urls)
+    }
+    
+    pub fn node_success_pct(&self) -> f64 {
+        percent(self.reachable_nodes, self.total_nodes)
+    }
+    
+    /// Why the run should exit non-zero, if it should: no working URLs at all,
+    /// or fewer reachable nodes than `fail_under`
+    pub fn failure_reason(&self, fail_under: Option<usize>) -> Option<String> {
+        if self.working_urls == 0 {
+            return Some(format!("no working subscription URLs out of {}", self.total_urls));
+        }
+        self.node_failure_reason(fail_under)
+    }
+    
+    /// Just the `fail_under` half, for `--nodes-file` runs that never had URLs to check
+    pub fn node_failure_reason(&self, fail_under: Option<usize>) -> Option<String> {
+        match fail_under {
+            Some(min) if self.reachable_nodes < min => {
+                Some(format!("only {} reachable nodes, --fail-under is {}", self.reachable_nodes, min))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `--dup-stats`: how much the subscriptions overlap
+#[derive(Debug, Clone, PartialEq)]
+pub struct DupStats {
+    pub total: usize,
+    pub unique: usize,
+    /// Most repeated nodes with how often they showed up, most first (ties by host/port)
+    pub top: Vec<(Node, usize)>,
+}
+
+impl DupStats {
+    pub fn new(nodes: &[Node], top_n: usize) -> Self {
+        let mut counts: HashMap<&Node, usize> = HashMap::new();
+        for node in nodes {
+            *counts.entry(node).or_default() += 1;
+        }
+        
+        let unique = counts.len();
+        let mut top: Vec<(Node, usize)> = counts
+            .into_iter()
+            .filter(|&(_, count)| count > 1)
+            .map(|(node, count)| (node.clone(), count))
+            .collect();
+        top.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| (&a.host, a.port).cmp(&(&b.host, b.port))));
+        top.truncate(top_n);
+        
+        Self { total: nodes.len(), unique, top }
+    }
+    
+    pub fn duplicates(&self) -> usize {
+        self.total - self.unique
+    }
+}
+
+/// `--source-stats`: how the nodes of one subscription did
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceStats {
+    pub source: String,
+    pub total: usize,
+    pub reachable: usize,
+    pub median_latency: Option<f64>,
+}
+
+impl SourceStats {
+    /// One entry per source, fastest median first (sources with nothing reachable last)
+    pub fn collect(node_results: &[NodeResult]) -> Vec<Self> {
+        let mut by_source: BTreeMap<&str, Vec<&NodeResult>> = BTreeMap::new();
+        for result in node_results {
+            if let Some(source) = &result.node.source {
+                by_source.entry(source).or_default().push(result);
+            }
+        }
+        
+        let mut stats: Vec<Self> = by_source
+            .into_iter()
+            .map(|(source, results)| {
+                let latencies: Vec<f64> = results.iter().filter(|r| r.is_working()).filter_map(|r| r.latency).collect();
+                Self {
+                    source: source.to_string(),
+                    total: results.len(),
+                    reachable: results.iter().filter(|r| r.is_working()).count(),
+                    median_latency: percentile(&latencies, 50.0),
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| match (a.median_latency, b.median_latency) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+        stats
+    }
 }
 
 pub struct RegexPatterns {
     pub url_regex: Regex,
     pub hostport_regex: Regex,
+    pub ipv6_hostport_regex: Regex,
     pub vmess_regex: Regex,
     pub vless_regex: Regex,
     pub trojan_regex: Regex,
     pub ss_regex: Regex,
+    pub ss_legacy_regex: Regex,
     pub ssr_regex: Regex,
-    pub json_inline_regex: Regex,
+    pub ssd_regex: Regex,
+    pub socks_regex: Regex,
+    pub naive_regex: Regex,
+    pub hysteria_regex: Regex,
 }
 
 impl RegexPatterns {
+    /// `--url-pattern`: swaps the URL extraction regex for the user's
+    pub fn with_url_pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.url_regex = Regex::new(pattern)?;
+        Ok(self)
+    }
+    
     pub fn new() -> Self {
         Self {
             url_regex: Regex::new(r"https?://[^\s)]+").unwrap(),
             hostport_regex: Regex::new(r"([0-9a-zA-Z.\-]+):(\d{2,5})").unwrap(),
-            vmess_regex: Regex::new(r"vmess://([A-Za-z0-9+/=]+)").unwrap(),
-            vless_regex: Regex::new(r"vless://[^@]+@([^/?#]+)").unwrap(),
-            trojan_regex: Regex::new(r"trojan://[^@]+@([^/?#]+)").unwrap(),
-            ss_regex: Regex::new(r"ss://[^@]+@([^/?#]+)").unwrap(),
-            ssr_regex: Regex::new(r"ssr://([A-Za-z0-9+/=]+)").unwrap(),
-            json_inline_regex: Regex::new(r"-\s*(\{[^}]*\})").unwrap(),
+            ipv6_hostport_regex: Regex::new(r"\[([0-9a-fA-F:]+)(%[^\]\s]*)?\]:(\d{2,5})").unwrap(),
+            vmess_regex: Regex::new(r"vmess://([A-Za-z0-9+/=_\-]+)").unwrap(),
+            vless_regex: Regex::new(r"vless://[^@\s]+@([^/?#\s]+)").unwrap(),
+            trojan_regex: Regex::new(r"trojan(?:-go)?://[^@\s]+@([^/?#\s]+)").unwrap(),
+            ss_regex: Regex::new(r"\bss://[^@\s]+@([^/?#\s]+)").unwrap(),
+            ss_legacy_regex: Regex::new(r"\bss://([A-Za-z0-9+/=_\-]+)(?:[/?#\s]|$)").unwrap(),
+            ssr_regex: Regex::new(r"ssr://([A-Za-z0-9+/=_\-]+)").unwrap(),
+            ssd_regex: Regex::new(r"ssd://([A-Za-z0-9+/=_\-]+)").unwrap(),
+            socks_regex: Regex::new(r"\bsocks5?://([^/?#\s]+)").unwrap(),
+            naive_regex: Regex::new(r"naive\+(?:https|quic)://(?:[^@\s]+@)?([^/?#\s]+)").unwrap(),
+            hysteria_regex: Regex::new(r"\bhysteria://(?:[^@/?#\s]+@)?([^/?#\s]+)").unwrap(),
         }
     }
 }
+
+impl Default for RegexPatterns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_status_histogram() {
+        let url = |status: Option<u16>, failure: Option<FailureKind>| UrlResult {
+            url: "https://a.example.com/sub".to_string(), status, latency: None, ttfb: None, failure,
+        };
+        let results = vec![
+            url(None, Some(FailureKind::Timeout)), url(Some(404), None), url(Some(200), None),
+            url(Some(403), None), url(Some(200), None), url(None, Some(FailureKind::DnsError)),
+            url(None, Some(FailureKind::Timeout)), url(Some(200), None),
+        ];
+        
+        let histogram = StatusHistogram::collect(&results);
+        assert_eq!(histogram.format(), "200: 3, 403: 1, 404: 1, DNS: 1, TIMEOUT: 2");
+        assert_eq!(histogram.0.iter().map(|(_, n)| n).sum::<usize>(), results.len());
+    }
+
+    #[test]
+    fn test_dup_stats_counts() {
+        let node = |host: &str| Node::new(host.to_string(), 443);
+        let nodes = vec![
+            node("a.example.com"), node("b.example.com"), node("A.example.com."),
+            node("c.example.com"), node("b.example.com"), node("a.example.com"),
+            node("d.example.com"),
+        ];
+        
+        let stats = DupStats::new(&nodes, 1);
+        assert_eq!((stats.total, stats.unique, stats.duplicates()), (7, 4, 3));
+        assert_eq!(stats.top, vec![(node("a.example.com"), 3)]);
+        assert_eq!(DupStats::new(&nodes, 10).top, vec![(node("a.example.com"), 3), (node("b.example.com"), 2)]);
+    }
+
+    #[test]
+    fn test_source_stats_medians() {
+        let result = |host: &str, source: &str, latency: Option<f64>| {
+            let node = Node::new(host.to_string(), 443).with_source(source);
+            NodeResult::new(node, latency.map(|_| 204), latency)
+        };
+        let results = vec![
+            result("a1.example.com", "https://slow.example.com/sub", Some(300.0)),
+            result("a2.example.com", "https://slow.example.com/sub", Some(500.0)),
+            result("a3.example.com", "https://slow.example.com/sub", None),
+            result("b1.example.com", "https://fast.example.com/sub", Some(40.0)),
+            result("b2.example.com", "https://fast.example.com/sub", Some(60.0)),
+            result("b3.example.com", "https://fast.example.com/sub", Some(80.0)),
+            result("c1.example.com", "https://dead.example.com/sub", None),
+            NodeResult::new(Node::new("untracked.example.com".to_string(), 443), Some(204), Some(1.0)),
+        ];
+        
+        let stats = SourceStats::collect(&results);
+        let summary: Vec<_> = stats.iter().map(|s| (s.source.as_str(), s.reachable, s.total, s.median_latency)).collect();
+        assert_eq!(summary, vec![
+            ("https://fast.example.com/sub", 3, 3, Some(60.0)),
+            ("https://slow.example.com/sub", 2, 3, Some(300.0)),
+            ("https://dead.example.com/sub", 0, 1, None),
+        ]);
+    }
+
+    #[test]
+    fn test_normalized_hosts_collapse() {
+        let nodes: HashSet<Node> = [
+            Node::new("Example.COM.".to_string(), 443),
+            Node::new("example.com".to_string(), 443),
+        ].into_iter().collect();
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_failure_reason() {
+        let summary = |working_urls, reachable_nodes| RunSummary {
+            total_urls: 10,
+            working_urls,
+            total_nodes: 50,
+            reachable_nodes,
+            median_latency: None,
+            p95_latency: None,
+            duration_secs: 1.0,
+        };
+        
+        assert!(summary(0, 0).failure_reason(None).unwrap().contains("no working subscription URLs"));
+        assert!(summary(0, 20).failure_reason(Some(5)).is_some());
+        assert_eq!(summary(3, 0).failure_reason(None), None);
+        assert!(summary(3, 4).failure_reason(Some(5)).unwrap().contains("only 4 reachable"));
+        assert_eq!(summary(3, 5).failure_reason(Some(5)), None);
+        assert_eq!(summary(3, 0).failure_reason(Some(0)), None);
+        assert_eq!(summary(0, 20).node_failure_reason(Some(5)), None);
+        assert!(summary(0, 4).node_failure_reason(Some(5)).is_some());
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::config::{ADAPTIVE_ERROR_THRESHOLD, ADAPTIVE_MIN_WORKERS, ADAPTIVE_START_WORKERS, ADAPTIVE_STEP, ADAPTIVE_WINDOW};
+
+/// Worker pool for the IO phases. `fixed` behaves like a plain semaphore; `adaptive`
+/// does AIMD on it: start small, add `ADAPTIVE_STEP` permits after every window of
+/// results that stays under the error threshold, halve when it doesn't.
+pub struct AdaptiveLimiter {
+    semaphore: Arc<Semaphore>,
+    adaptive: bool,
+    max: usize,
+    state: Mutex<AdaptiveState>,
+}
+
+struct AdaptiveState {
+    limit: usize,
+    /// Permits we still owe after a cut, taken back as in-flight work finishes
+    debt: usize,
+    recent: VecDeque<bool>,
+}
+
+/// Held while a request is in flight
+pub struct AdaptivePermit {
+    permit: Option<OwnedSemaphorePermit>,
+    limiter: Arc<AdaptiveLimiter>,
+}
+
+impl Drop for AdaptivePermit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        if state.debt > 0 {
+            state.debt -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+impl AdaptiveLimiter {
+    pub fn fixed(workers: usize) -> Self {
+        Self::with_limit(workers, workers, false)
+    }
+    
+    pub fn adaptive(max_workers: usize) -> Self {
+        Self::with_limit(ADAPTIVE_START_WORKERS.min(max_workers), max_workers, true)
+    }
+    
+    fn with_limit(limit: usize, max: usize, adaptive: bool) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            adaptive,
+            max,
+            state: Mutex::new(AdaptiveState { limit, debt: 0, recent: VecDeque::new() }),
+        }
+    }
+    
+    pub async fn acquire(self: &Arc<Self>) -> AdaptivePermit {
+        let permit = self.semaphore.clone().acquire_owned().await.unwrap();
+        AdaptivePermit { permit: Some(permit), limiter: self.clone() }
+    }
+    
+    /// Current concurrency target
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+    
+    /// Feed back whether a request succeeded. Timeouts and errors count as failures.
+    pub fn record(&self, success: bool) {
+        if !self.adaptive {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.recent.push_back(success);
+        if state.recent.len() < ADAPTIVE_WINDOW {
+            return;
+        }
+        
+        let failures = state.recent.iter().filter(|ok| !**ok).count();
+        let error_rate = failures as f64 / state.recent.len() as f64;
+        state.recent.clear();
+        
+        if error_rate > ADAPTIVE_ERROR_THRESHOLD {
+            let target = (state.limit / 2).max(ADAPTIVE_MIN_WORKERS).min(state.limit);
+            let cut = state.limit - target;
+            let forgotten = self.semaphore.forget_permits(cut);
+            state.debt += cut - forgotten;
+            state.limit = target;
+        } else if state.limit < self.max {
+            let target = (state.limit + ADAPTIVE_STEP).min(self.max);
+            let mut grow = target - state.limit;
+            let repaid = grow.min(state.debt);
+            state.debt -= repaid;
+            grow -= repaid;
+            self.semaphore.add_permits(grow);
+            state.limit = target;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_window(limiter: &AdaptiveLimiter, failures: usize) {
+        for i in 0..ADAPTIVE_WINDOW {
+            limiter.record(i >= failures);
+        }
+    }
+
+    #[test]
+    fn test_backs_off_when_errors_rise() {
+        let limiter = AdaptiveLimiter::adaptive(100);
+        assert_eq!(limiter.limit(), ADAPTIVE_START_WORKERS);
+        
+        record_window(&limiter, 0);
+        record_window(&limiter, 0);
+        let grown = ADAPTIVE_START_WORKERS + 2 * ADAPTIVE_STEP;
+        assert_eq!(limiter.limit(), grown);
+        
+        // error rate climbing past the threshold halves it each window
+        record_window(&limiter, ADAPTIVE_WINDOW / 2);
+        assert_eq!(limiter.limit(), grown / 2);
+        record_window(&limiter, ADAPTIVE_WINDOW);
+        assert_eq!(limiter.limit(), grown / 4);
+        for _ in 0..10 {
+            record_window(&limiter, ADAPTIVE_WINDOW);
+        }
+        assert_eq!(limiter.limit(), ADAPTIVE_MIN_WORKERS);
+        assert_eq!(limiter.semaphore.available_permits(), ADAPTIVE_MIN_WORKERS);
+    }
+
+    #[tokio::test]
+    async fn test_cut_while_permits_held() {
+        let limiter = Arc::new(AdaptiveLimiter::adaptive(100));
+        let mut held = Vec::new();
+        for _ in 0..ADAPTIVE_START_WORKERS {
+            held.push(limiter.acquire().await);
+        }
+        
+        record_window(&limiter, ADAPTIVE_WINDOW);
+        assert_eq!(limiter.limit(), ADAPTIVE_START_WORKERS / 2);
+        drop(held);
+        assert_eq!(limiter.semaphore.available_permits(), ADAPTIVE_START_WORKERS / 2);
+    }
+
+    #[test]
+    fn test_fixed_never_changes() {
+        let limiter = AdaptiveLimiter::fixed(10);
+        record_window(&limiter, ADAPTIVE_WINDOW);
+        assert_eq!(limiter.limit(), 10);
+        assert_eq!(limiter.semaphore.available_permits(), 10);
+    }
+}
@@ -1,44 +1,222 @@
-use reqwest::Client;
+use reqwest::{Client, Proxy};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::time::timeout;
-use crate::models::{UrlResult, NodeResult, Node};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::{timeout, timeout_at};
+use crate::config::{HttpMethod, MAX_CHECK_BODY_BYTES};
+use crate::models::{FailureKind, UrlResult, NodeResult, Node};
+use crate::network::failure::{classify_io_error, classify_reqwest_error};
+use crate::network::retry::{retry_with_backoff, Retries};
+use crate::network::dns::{DnsCache, DnsLimiter};
+use crate::utils::percentile;
+use tracing::debug;
 
-pub async fn http_check(client: &Client, url: &str, timeout_duration: Duration) -> UrlResult {
-    let start = Instant::now();
-    
-    let result = timeout(timeout_duration, async {
-        // Try HEAD first, then GET if it fails
-        let response = client.head(url).send().await;
-        match response {
-            Ok(resp) if resp.status().as_u16() < 400 => Ok(resp),
-            _ => client.get(url).send().await,
+pub async fn http_check(client: &Client, url: &str, method: HttpMethod, timeout_duration: Duration, retries: &Retries) -> UrlResult {
+    let result = retry_with_backoff(retries, || async {
+        let start = Instant::now();
+        let result = timeout(timeout_duration, async {
+            let mut response = match method {
+                HttpMethod::Head => client.head(url).send().await?,
+                HttpMethod::Get => client.get(url).send().await?,
+                // Try HEAD first, then GET if it fails
+                HttpMethod::Auto => match client.head(url).send().await {
+                    Ok(resp) if resp.status().as_u16() < 400 => resp,
+                    _ => client.get(url).send().await?,
+                },
+            };
+            
+            // send() resolves once the headers are in, the body is still streaming
+            let ttfb = start.elapsed().as_secs_f64() * 1000.0;
+            let status = response.status().as_u16();
+            let mut read = 0;
+            while read < MAX_CHECK_BODY_BYTES {
+                match response.chunk().await? {
+                    Some(chunk) => read += chunk.len(),
+                    None => break,
+                }
+            }
+            Ok::<_, reqwest::Error>((status, ttfb))
+        }).await;
+        
+        match result {
+            Ok(Ok((status, ttfb))) => Ok((status, ttfb, start.elapsed().as_secs_f64() * 1000.0)),
+            Ok(Err(e)) => Err(classify_reqwest_error(&e)),
+            Err(_) => Err(FailureKind::Timeout),
         }
     }).await;
     
-    let latency = start.elapsed().as_secs_f64() * 1000.0;
-    
     match result {
-        Ok(Ok(response)) => UrlResult {
+        Ok((status, ttfb, latency)) => UrlResult {
             url: url.to_string(),
-            status: Some(response.status().as_u16()),
+            status: Some(status),
             latency: Some(latency),
+            ttfb: Some(ttfb),
+            failure: None,
         },
-        _ => UrlResult {
+        Err(failure) => UrlResult {
             url: url.to_string(),
             status: None,
             latency: None,
+            ttfb: None,
+            failure: Some(failure),
         },
     }
 }
 
-pub async fn node_http_check(client: &Client, node: Node, timeout_duration: Duration) -> NodeResult {
+pub async fn node_http_check(client: &Client, node: Node, method: HttpMethod, timeout_duration: Duration, retries: &Retries) -> NodeResult {
     let url = node.url();
-    let result = http_check(client, &url, timeout_duration).await;
+    let result = http_check(client, &url, method, timeout_duration, retries).await;
     
     NodeResult {
-        node,
-        status: result.status,
-        latency: result.latency,
+        ttfb: result.ttfb,
+        failure: result.failure,
+        ..NodeResult::new(node, result.status, result.latency)
+    }
+}
+
+/// `--confirm`: a failure, or a pass slower than `fraction` of the timeout, gets a second try
+pub fn needs_confirm(result: &NodeResult, timeout_duration: Duration, fraction: f64) -> bool {
+    let borderline_ms = timeout_duration.as_secs_f64() * 1000.0 * fraction;
+    !result.is_working() || result.latency.is_some_and(|latency| latency > borderline_ms)
+}
+
+/// The better of two checks of the same node: working beats failing, then the lower latency
+pub fn better_result(first: NodeResult, second: NodeResult) -> NodeResult {
+    let latency = |result: &NodeResult| result.latency.unwrap_or(f64::INFINITY);
+    match (first.is_working(), second.is_working()) {
+        (false, true) => second,
+        (true, true) if latency(&second) < latency(&first) => second,
+        _ => first,
+    }
+}
+
+/// Runs `check` on the node, and with `confirm_fraction` set runs it once more when the
+/// first result was borderline (see [`needs_confirm`]), keeping the better of the two.
+pub async fn check_with_confirm<F, Fut>(node: Node, timeout_duration: Duration, confirm_fraction: Option<f64>, check: F) -> NodeResult
+where
+    F: Fn(Node) -> Fut,
+    Fut: Future<Output = NodeResult>,
+{
+    let first = check(node).await;
+    match confirm_fraction {
+        Some(fraction) if needs_confirm(&first, timeout_duration, fraction) => {
+            let second = check(first.node.clone()).await;
+            debug!("Confirm {}: {:?} -> {:?}", first.node.url(), first.status, second.status);
+            better_result(first, second)
+        }
+        _ => first,
+    }
+}
+
+pub fn build_proxied_client(node: &Node, timeout_duration: Duration, dns: &DnsLimiter) -> reqwest::Result<Client> {
+    Client::builder()
+        .proxy(Proxy::all(node.url())?)
+        .dns_resolver(Arc::new(dns.clone()))
+        .timeout(timeout_duration)
+        .build()
+}
+
+/// Requests every probe URL through the node as an HTTP proxy, all at once. Only a 204
+/// counts as the node actually relaying traffic; the node works if at least `quorum`
+/// probes pass, and its latency is the median of the ones that did.
+pub async fn node_proxy_check(node: Node, probe_urls: &[String], quorum: usize, timeout_duration: Duration, retries: &Retries, dns: &DnsLimiter) -> NodeResult {
+    let client = match build_proxied_client(&node, timeout_duration, dns) {
+        Ok(client) => client,
+        Err(_) => return NodeResult::new(node, None, None).with_failure(FailureKind::Other),
+    };
+    
+    let probes: Vec<_> = probe_urls
+        .iter()
+        .map(|url| tokio::spawn(probe_through(client.clone(), url.clone(), timeout_duration, retries.clone())))
+        .collect();
+    let mut outcomes = Vec::new();
+    for probe in probes {
+        outcomes.push(probe.await.unwrap_or(Err(FailureKind::Other)));
+    }
+    
+    let passed: Vec<f64> = outcomes.iter().filter_map(|o| o.as_ref().ok().copied()).collect();
+    if probe_urls.len() > 1 {
+        let marks: Vec<String> = probe_urls
+            .iter()
+            .zip(&outcomes)
+            .map(|(url, outcome)| format!("{} {}", if outcome.is_ok() { "✓" } else { "✗" }, url))
+            .collect();
+        debug!("{}:{} passed {}/{} probes: {}", node.authority_host(), node.port, passed.len(), probe_urls.len(), marks.join(", "));
+    }
+    
+    if passed.len() >= quorum.max(1) {
+        NodeResult::new(node, Some(204), percentile(&passed, 50.0))
+    } else {
+        let failure = outcomes.into_iter().find_map(Result::err).unwrap_or(FailureKind::Other);
+        NodeResult::new(node, None, None).with_failure(failure)
+    }
+}
+
+/// `--speed-test`: downloads up to `max_bytes` from `url` through the node and returns MB/s.
+/// Whatever arrived before the timeout still counts, `None` if nothing did.
+pub async fn node_speed_test(node: &Node, url: &str, max_bytes: u64, timeout_duration: Duration, dns: &DnsLimiter) -> Option<f64> {
+    let client = build_proxied_client(node, timeout_duration, dns).ok()?;
+    let start = Instant::now();
+    let deadline = tokio::time::Instant::now() + timeout_duration;
+    let mut received = 0u64;
+    
+    if let Ok(Ok(mut response)) = timeout_at(deadline, client.get(url).send()).await {
+        if response.status().is_success() {
+            while received < max_bytes {
+                match timeout_at(deadline, response.chunk()).await {
+                    Ok(Ok(Some(chunk))) => received += chunk.len() as u64,
+                    _ => break,
+                }
+            }
+        }
+    }
+    
+    let elapsed = start.elapsed().as_secs_f64();
+    (received > 0).then(|| received as f64 / 1_000_000.0 / elapsed)
+}
+
+async fn probe_through(client: Client, probe_url: String, timeout_duration: Duration, retries: Retries) -> Result<f64, FailureKind> {
+    retry_with_backoff(&retries, || async {
+        let start = Instant::now();
+        match timeout(timeout_duration, client.get(&probe_url).send()).await {
+            Ok(Ok(response)) if response.status().as_u16() == 204 => Ok(start.elapsed().as_secs_f64() * 1000.0),
+            Ok(Ok(_)) => Err(FailureKind::Other),
+            Ok(Err(e)) => Err(classify_reqwest_error(&e)),
+            Err(_) => Err(FailureKind::Timeout),
+        }
+    }).await
+}
+
+/// TCP connection to the node, resolving through the DNS cache when there is one
+pub(crate) async fn connect_node(node: &Node, dns_cache: Option<&DnsCache>, dns: &DnsLimiter) -> std::io::Result<TcpStream> {
+    let addrs: Vec<SocketAddr> = match dns_cache {
+        Some(cache) => cache
+            .lookup(&node.host, |host| dns.clone().resolve_host_all(host))
+            .await
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, node.port))
+            .collect(),
+        None => dns.run(lookup_host((node.host.as_str(), node.port))).await?.collect(),
+    };
+    TcpStream::connect(&addrs[..]).await
+}
+
+pub async fn tcp_connect_check(node: Node, timeout_duration: Duration, retries: &Retries, dns_cache: Option<&DnsCache>, dns: &DnsLimiter) -> NodeResult {
+    let result = retry_with_backoff(retries, || async {
+        let start = Instant::now();
+        match timeout(timeout_duration, connect_node(&node, dns_cache, dns)).await {
+            Ok(Ok(_stream)) => Ok(start.elapsed().as_secs_f64() * 1000.0),
+            Ok(Err(e)) => Err(classify_io_error(&e)),
+            Err(_) => Err(FailureKind::Timeout),
+        }
+    }).await;
+    
+    match result {
+        // status 0 just means "handshake went through", there's no HTTP status here
+        Ok(latency) => NodeResult::new(node, Some(0), Some(latency)),
+        Err(failure) => NodeResult::new(node, None, None).with_failure(failure),
     }
 }
 
@@ -52,6 +230,178 @@ mod tests {
     async fn test_node_http_check() {
         let client = Client::new();
         let node = crate::models::Node::new("127.0.0.1".to_string(), 8080);
-        let _ = node_http_check(&client, node, Duration::from_secs(1)).await;
+        let _ = node_http_check(&client, node, HttpMethod::Auto, Duration::from_secs(1), &Retries::new(0)).await;
+    }
+
+    #[tokio::test]
+    async fn test_http_check_ttfb_before_body() {
+        use crate::network::mock::{serve, MockResponse};
+        
+        let addr = serve(|req| {
+            if req.method == "HEAD" {
+                MockResponse::new(405, "")
+            } else {
+                MockResponse::new(200, "slow body").body_delay(Duration::from_millis(300))
+            }
+        }).await;
+        
+        let result = http_check(&Client::new(), &format!("http://{}/", addr), HttpMethod::Auto, Duration::from_secs(2), &Retries::new(0)).await;
+        assert_eq!(result.status, Some(200));
+        let (ttfb, latency) = (result.ttfb.unwrap(), result.latency.unwrap());
+        assert!(latency >= 300.0);
+        assert!(ttfb < latency - 200.0);
+    }
+
+    #[tokio::test]
+    async fn test_http_check_fails_on_cut_off_body() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        
+        // promises 100 bytes, sends 5 and hangs up
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nshort").await;
+            }
+        });
+        
+        let result = http_check(&Client::new(), &format!("http://{}/", addr), HttpMethod::Get, Duration::from_secs(2), &Retries::new(0)).await;
+        assert_eq!(result.status, None);
+        assert!(result.latency.is_none());
+        assert!(result.failure.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_confirm_keeps_the_retest_pass() {
+        use crate::network::mock::{serve, MockResponse};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        
+        // first GET stalls past the timeout, the re-test answers right away
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let addr = serve(move |_| {
+            let response = MockResponse::new(200, "ok");
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                response.body_delay(Duration::from_secs(2))
+            } else {
+                response
+            }
+        }).await;
+        
+        let client = Client::new();
+        let node = Node::new(addr.ip().to_string(), addr.port());
+        let timeout = Duration::from_millis(300);
+        let retries = Retries::new(0);
+        let (client, retries) = (&client, &retries);
+        let check = move |node| node_http_check(client, node, HttpMethod::Get, timeout, retries);
+        
+        let result = check_with_confirm(node, timeout, Some(0.8), check).await;
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(result.status, Some(200));
+        assert_eq!(result.failure, None);
+    }
+
+    #[test]
+    fn test_needs_confirm() {
+        let node = Node::new("a.example.com".to_string(), 80);
+        let timeout = Duration::from_secs(1);
+        assert!(needs_confirm(&NodeResult::new(node.clone(), None, None), timeout, 0.8));
+        assert!(needs_confirm(&NodeResult::new(node.clone(), Some(200), Some(900.0)), timeout, 0.8));
+        assert!(!needs_confirm(&NodeResult::new(node, Some(200), Some(100.0)), timeout, 0.8));
+    }
+
+    async fn methods_sent(method: HttpMethod, head_status: u16) -> (Option<u16>, Vec<String>) {
+        use crate::network::mock::{serve, MockResponse};
+        use std::sync::{Arc, Mutex};
+        
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+        let addr = serve(move |req| {
+            recorder.lock().unwrap().push(req.method.clone());
+            MockResponse::new(if req.method == "HEAD" { head_status } else { 200 }, "")
+        }).await;
+        
+        let result = http_check(&Client::new(), &format!("http://{}/", addr), method, Duration::from_secs(2), &Retries::new(0)).await;
+        let methods = seen.lock().unwrap().clone();
+        (result.status, methods)
+    }
+
+    #[tokio::test]
+    async fn test_http_check_methods() {
+        assert_eq!(methods_sent(HttpMethod::Auto, 200).await, (Some(200), vec!["HEAD".to_string()]));
+        assert_eq!(methods_sent(HttpMethod::Auto, 405).await, (Some(200), vec!["HEAD".to_string(), "GET".to_string()]));
+        assert_eq!(methods_sent(HttpMethod::Get, 405).await, (Some(200), vec!["GET".to_string()]));
+        assert_eq!(methods_sent(HttpMethod::Head, 405).await, (Some(405), vec!["HEAD".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_node_proxy_check_quorum() {
+        use crate::network::mock::{serve, MockResponse};
+        
+        // the mock is the node: a plain HTTP proxy sees the absolute probe URL as the path
+        let addr = serve(|req| {
+            if req.path.contains("up.example.com") {
+                MockResponse::new(204, "")
+            } else {
+                MockResponse::new(503, "blocked")
+            }
+        }).await;
+        let node = || Node::new("127.0.0.1".to_string(), addr.port());
+        let probes = vec!["http://up.example.com/generate_204".to_string(), "http://down.example.com/generate_204".to_string()];
+        
+        let one = node_proxy_check(node(), &probes, 1, Duration::from_secs(2), &Retries::new(0), &DnsLimiter::new(1)).await;
+        assert_eq!(one.status, Some(204));
+        assert!(one.latency.is_some());
+        
+        let both = node_proxy_check(node(), &probes, 2, Duration::from_secs(2), &Retries::new(0), &DnsLimiter::new(1)).await;
+        assert!(!both.is_working());
+        assert_eq!(both.failure, Some(FailureKind::Other));
+    }
+
+    #[tokio::test]
+    async fn test_node_speed_test() {
+        use crate::network::mock::{serve, MockResponse};
+        
+        let addr = serve(|req| {
+            if req.path.contains("speed.example.com") {
+                MockResponse::new(200, vec![0u8; 256 * 1024])
+            } else {
+                MockResponse::new(404, "")
+            }
+        }).await;
+        let node = Node::new("127.0.0.1".to_string(), addr.port());
+        
+        let throughput = node_speed_test(&node, "http://speed.example.com/payload", 1024 * 1024, Duration::from_secs(5), &DnsLimiter::new(1)).await;
+        assert!(throughput.is_some_and(|mb_s| mb_s > 0.0), "{:?}", throughput);
+        
+        let missing = node_speed_test(&node, "http://other.example.com/payload", 1024 * 1024, Duration::from_secs(5), &DnsLimiter::new(1)).await;
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn test_build_proxied_client() {
+        let node = crate::models::Node::new("127.0.0.1".to_string(), 8080);
+        assert!(build_proxied_client(&node, Duration::from_secs(1), &DnsLimiter::new(1)).is_ok());
+        let node = crate::models::Node::new("2001:db8::1".to_string(), 443);
+        assert!(build_proxied_client(&node, Duration::from_secs(1), &DnsLimiter::new(1)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connect_check_closed_port() {
+        // grab a free port and release it so nothing is listening there
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let node = crate::models::Node::new("127.0.0.1".to_string(), port);
+        let start = Instant::now();
+        let result = tcp_connect_check(node, Duration::from_secs(2), &Retries::new(0), None, &DnsLimiter::new(1)).await;
+        assert_eq!(result.status, None);
+        assert_eq!(result.latency, None);
+        assert_eq!(result.failure, Some(FailureKind::ConnectionRefused));
+        assert!(start.elapsed() < Duration::from_secs(1));
     }
 }
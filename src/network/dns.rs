@@ -0,0 +1,264 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use tokio::net::lookup_host;
+use tokio::sync::Semaphore;
+use crate::models::Node;
+
+pub async fn resolve_host(host: String) -> Option<IpAddr> {
+    lookup_host((host.as_str(), 0)).await.ok()?.next().map(|addr| addr.ip())
+}
+
+pub async fn resolve_host_all(host: String) -> Vec<IpAddr> {
+    match lookup_host((host.as_str(), 0)).await {
+        Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// `--max-dns-workers`: how many lookups may be in flight at once, whichever pool asked
+/// for them. Keeps thousands of node checks from flooding the resolver.
+#[derive(Clone)]
+pub struct DnsLimiter {
+    semaphore: Arc<Semaphore>,
+}
+
+impl DnsLimiter {
+    pub fn new(max_workers: usize) -> Self {
+        Self { semaphore: Arc::new(Semaphore::new(max_workers)) }
+    }
+    
+    /// Runs `lookup` once a slot is free
+    pub async fn run<Fut: Future>(&self, lookup: Fut) -> Fut::Output {
+        let _permit = self.semaphore.acquire().await.unwrap();
+        lookup.await
+    }
+    
+    /// `resolve_host` behind the limit. Takes `self` so `|host| dns.clone().resolve_host(host)`
+    /// fits wherever a resolver is passed in.
+    pub async fn resolve_host(self, host: String) -> Option<IpAddr> {
+        self.run(resolve_host(host)).await
+    }
+    
+    pub async fn resolve_host_all(self, host: String) -> Vec<IpAddr> {
+        self.run(resolve_host_all(host)).await
+    }
+}
+
+/// Lets reqwest clients resolve through the same limit as everything else
+impl Resolve for DnsLimiter {
+    fn resolve(&self, name: Name) -> Resolving {
+        let limiter = self.clone();
+        Box::pin(async move {
+            let addrs: Vec<_> = limiter.run(lookup_host((name.as_str(), 0))).await?.collect();
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+type CachedIps = (Vec<IpAddr>, Instant);
+
+/// Shared host -> IPs cache so the same host isn't re-resolved for every node.
+/// Entries expire after `ttl` and the map never grows past `max_entries`.
+pub struct DnsCache {
+    entries: Mutex<HashMap<String, CachedIps>>,
+    ttl: Duration,
+    max_entries: usize,
+}
+
+impl DnsCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            max_entries,
+        }
+    }
+    
+    pub async fn lookup<F, Fut>(&self, host: &str, resolve: F) -> Vec<IpAddr>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Vec<IpAddr>>,
+    {
+        if let Some((ips, resolved_at)) = self.entries.lock().unwrap().get(host) {
+            if resolved_at.elapsed() < self.ttl {
+                return ips.clone();
+            }
+        }
+        
+        let ips = resolve(host.to_string()).await;
+        if ips.is_empty() {
+            return ips;
+        }
+        
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries {
+            let ttl = self.ttl;
+            entries.retain(|_, (_, resolved_at)| resolved_at.elapsed() < ttl);
+        }
+        if entries.len() < self.max_entries || entries.contains_key(host) {
+            entries.insert(host.to_string(), (ips.clone(), Instant::now()));
+        }
+        
+        ips
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Resolves every unique host once and returns the host -> IP map. Hosts that
+/// don't resolve are simply missing from the map.
+pub async fn resolve_all<F, Fut>(nodes: &[Node], resolve: F) -> HashMap<String, IpAddr>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Option<IpAddr>> + Send + 'static,
+{
+    let hosts: HashSet<&String> = nodes.iter().map(|n| &n.host).collect();
+    
+    let mut tasks = Vec::new();
+    for host in hosts {
+        tasks.push((host.clone(), tokio::spawn(resolve(host.clone()))));
+    }
+    
+    let mut resolved = HashMap::new();
+    for (host, task) in tasks {
+        if let Ok(Some(ip)) = task.await {
+            resolved.insert(host, ip);
+        }
+    }
+    
+    resolved
+}
+
+/// Collapses nodes whose hosts resolve to the same IP on the same port, keeping the
+/// first one seen. Unresolvable hosts are left alone.
+pub async fn dedup_by_resolved_ip<F, Fut>(nodes: Vec<Node>, resolve: F) -> Vec<Node>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Option<IpAddr>> + Send + 'static,
+{
+    let resolved = resolve_all(&nodes, resolve).await;
+    let mut seen = HashSet::new();
+    
+    nodes
+        .into_iter()
+        .filter(|node| match resolved.get(&node.host) {
+            Some(ip) => seen.insert((*ip, node.port)),
+            None => true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn fake_resolve(host: String) -> Option<IpAddr> {
+        match host.as_str() {
+            "a.example.com" | "b.example.com" => Some("203.0.113.7".parse().unwrap()),
+            "c.example.com" => Some("203.0.113.8".parse().unwrap()),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dedup_by_resolved_ip() {
+        let nodes = vec![
+            Node::new("a.example.com".to_string(), 443),
+            Node::new("b.example.com".to_string(), 443),
+            Node::new("b.example.com".to_string(), 8443),
+            Node::new("c.example.com".to_string(), 443),
+            Node::new("nowhere.invalid".to_string(), 443),
+        ];
+        
+        let deduped = dedup_by_resolved_ip(nodes, fake_resolve).await;
+        assert_eq!(deduped, vec![
+            Node::new("a.example.com".to_string(), 443),
+            Node::new("b.example.com".to_string(), 8443),
+            Node::new("c.example.com".to_string(), 443),
+            Node::new("nowhere.invalid".to_string(), 443),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_dns_limiter_caps_concurrency() {
+        let limiter = DnsLimiter::new(3);
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let resolver = |_host: String| {
+            let (limiter, active, peak) = (limiter.clone(), active.clone(), peak.clone());
+            async move {
+                limiter.run(async {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    Some("203.0.113.7".parse().unwrap())
+                }).await
+            }
+        };
+        
+        let nodes: Vec<Node> = (0..20).map(|i| Node::new(format!("h{}.example.com", i), 443)).collect();
+        let resolved = resolve_all(&nodes, resolver).await;
+        assert_eq!(resolved.len(), 20);
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_reqwest_resolves_through_limiter() {
+        let limiter = DnsLimiter::new(1);
+        let client = reqwest::Client::builder().dns_resolver(Arc::new(limiter.clone())).build().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        
+        // the only slot is busy, so the request can't even start connecting
+        let busy = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.run(tokio::time::sleep(Duration::from_millis(300))).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let request = tokio::spawn(async move { client.get(format!("http://localhost:{}/", port)).send().await });
+        let accepted = tokio::time::timeout(Duration::from_millis(150), listener.accept()).await;
+        assert!(accepted.is_err());
+        
+        busy.await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_secs(2), listener.accept()).await.is_ok());
+        request.abort();
+    }
+
+    #[tokio::test]
+    async fn test_dns_cache_hit() {
+        let cache = DnsCache::new(Duration::from_secs(60), 10);
+        let calls = AtomicUsize::new(0);
+        let resolver = |_host: String| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { vec!["203.0.113.7".parse().unwrap()] }
+        };
+        
+        let first = cache.lookup("a.example.com", resolver).await;
+        let second = cache.lookup("a.example.com", resolver).await;
+        assert_eq!(first, second);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dns_cache_bounded() {
+        let cache = DnsCache::new(Duration::from_secs(60), 2);
+        for host in ["a", "b", "c", "d"] {
+            cache.lookup(host, |_| async { vec!["203.0.113.7".parse().unwrap()] }).await;
+        }
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,114 @@
+use std::error::Error;
+use std::io;
+use crate::models::FailureKind;
+
+/// Sorts a failed request into a `FailureKind`. reqwest only flags timeouts and connect
+/// errors itself, DNS and TLS problems have to be read off the source chain.
+pub fn classify_reqwest_error(error: &reqwest::Error) -> FailureKind {
+    if error.is_timeout() {
+        return FailureKind::Timeout;
+    }
+    classify_chain(error)
+}
+
+/// Same for a raw TCP connect
+pub fn classify_io_error(error: &io::Error) -> FailureKind {
+    match error.kind() {
+        io::ErrorKind::TimedOut => FailureKind::Timeout,
+        io::ErrorKind::ConnectionRefused => FailureKind::ConnectionRefused,
+        _ => classify_chain(error),
+    }
+}
+
+fn classify_chain(error: &(dyn Error + 'static)) -> FailureKind {
+    let mut source: Option<&(dyn Error + 'static)> = Some(error);
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<io::Error>() {
+            match io_err.kind() {
+                io::ErrorKind::ConnectionRefused => return FailureKind::ConnectionRefused,
+                io::ErrorKind::TimedOut => return FailureKind::Timeout,
+                _ => {}
+            }
+        }
+        
+        let message = err.to_string().to_lowercase();
+        if message.contains("dns error") || message.contains("failed to lookup address") || message.contains("could not resolve") {
+            return FailureKind::DnsError;
+        }
+        if message.contains("certificate") || message.contains("tls") || message.contains("ssl") || message.contains("handshake") {
+            return FailureKind::Tls;
+        }
+        source = err.source();
+    }
+    FailureKind::Other
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+    use std::time::Duration;
+    use crate::network::mock::{serve, MockResponse};
+
+    async fn request_error(client: &Client, url: &str) -> reqwest::Error {
+        client.get(url).send().await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_classify_timeout() {
+        let addr = serve(|_| MockResponse::new(200, "late").body_delay(Duration::from_secs(2))).await;
+        let client = Client::builder().timeout(Duration::from_millis(200)).build().unwrap();
+        
+        let error = match client.get(format!("http://{}/", addr)).send().await {
+            Ok(response) => response.bytes().await.unwrap_err(),
+            Err(e) => e,
+        };
+        assert_eq!(classify_reqwest_error(&error), FailureKind::Timeout);
+    }
+
+    #[tokio::test]
+    async fn test_classify_connection_refused() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        
+        let error = request_error(&Client::new(), &format!("http://127.0.0.1:{}/", port)).await;
+        assert_eq!(classify_reqwest_error(&error), FailureKind::ConnectionRefused);
+        
+        let io_error = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap_err();
+        assert_eq!(classify_io_error(&io_error), FailureKind::ConnectionRefused);
+    }
+
+    #[tokio::test]
+    async fn test_classify_dns_error() {
+        // .invalid never resolves
+        let error = request_error(&Client::new(), "http://no-such-host.invalid/").await;
+        assert_eq!(classify_reqwest_error(&error), FailureKind::DnsError);
+        
+        let io_error = tokio::net::TcpStream::connect(("no-such-host.invalid", 80)).await.unwrap_err();
+        assert_eq!(classify_io_error(&io_error), FailureKind::DnsError);
+    }
+
+    #[tokio::test]
+    async fn test_classify_tls() {
+        // answers the ClientHello with plain HTTP, so the handshake falls over
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            if let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n").await;
+            }
+        });
+        
+        let client = Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+        let error = request_error(&client, &format!("https://{}/", addr)).await;
+        assert_eq!(classify_reqwest_error(&error), FailureKind::Tls);
+    }
+
+    #[test]
+    fn test_classify_other() {
+        let error = Client::new().get("http://").build().unwrap_err();
+        assert_eq!(classify_reqwest_error(&error), FailureKind::Other);
+    }
+}
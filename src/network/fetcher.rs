@@ -1,18 +1,85 @@
 use reqwest::Client;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
 use std::time::Duration;
-use tokio::time::timeout;
+use tracing::{debug, warn};
 
+/// GETs `url` and returns its body, `None` on timeout or error. An empty body gets
+/// one more try since some servers answer a warmed-up connection with nothing.
 pub async fn fetch_body(client: &Client, url: &str, timeout_duration: Duration) -> (String, Option<String>) {
-    let result = timeout(timeout_duration, client.get(url).send()).await;
+    (url.to_string(), try_fetch_body(client, url, timeout_duration).await.ok())
+}
+
+/// `fetch_body` that keeps the reason it failed
+pub async fn try_fetch_body(client: &Client, url: &str, timeout_duration: Duration) -> Result<String, reqwest::Error> {
+    let body = fetch_once(client, url, timeout_duration).await?;
+    if body.is_empty() {
+        debug!("{} - empty body, retrying once", url);
+        return fetch_once(client, url, timeout_duration).await;
+    }
+    Ok(body)
+}
+
+async fn fetch_once(client: &Client, url: &str, timeout_duration: Duration) -> Result<String, reqwest::Error> {
+    let response = client.get(url).timeout(timeout_duration).send().await?;
+    
+    // reqwest strips Content-Encoding once it has decoded the body, so anything left is unhandled
+    if let Some(encoding) = response.headers().get(CONTENT_ENCODING) {
+        if encoding != "identity" {
+            warn!("{} - unhandled Content-Encoding {:?}, body may be garbage", url, encoding);
+        }
+    }
+    if let Some(content_type) = response.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+        debug!("{} - Content-Type {}", url, content_type);
+        if content_type.starts_with("text/html") {
+            warn!("{} - got an HTML page rather than a subscription", url);
+        }
+    }
     
-    match result {
-        Ok(Ok(response)) => {
-            if let Ok(text) = response.text().await {
-                (url.to_string(), Some(text))
+    response.text().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, RegexPatterns};
+    use crate::network::mock::{serve, MockResponse};
+    use crate::parsers::detect_format_and_parse;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_fetch_body_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"trojan://secret@gz.example.com:443#node").unwrap();
+        let compressed = encoder.finish().unwrap();
+        
+        let addr = serve(move |_| MockResponse::new(200, compressed.clone()).header("Content-Encoding", "gzip")).await;
+        let client = Client::builder().gzip(true).build().unwrap();
+        
+        let (_, body) = fetch_body(&client, &format!("http://{}/sub", addr), Duration::from_secs(2)).await;
+        let body = body.unwrap();
+        let (nodes, _) = detect_format_and_parse(&body, &RegexPatterns::new(), &[], None);
+        assert_eq!(nodes, vec![Node::new("gz.example.com".to_string(), 443)]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_body_retries_empty_body() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let addr = serve(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                MockResponse::new(200, "")
             } else {
-                (url.to_string(), None)
+                MockResponse::new(200, "vless://id@retry.example.com:443").header("Content-Type", "text/plain")
             }
-        }
-        _ => (url.to_string(), None),
+        }).await;
+        
+        let (_, body) = fetch_body(&Client::new(), &format!("http://{}/sub", addr), Duration::from_secs(2)).await;
+        assert_eq!(body.as_deref(), Some("vless://id@retry.example.com:443"));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
     }
 }
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use governor::{DefaultDirectRateLimiter, Quota};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// At most `limit` concurrent permits per host, on top of the global worker pool
+pub struct HostLimiter {
+    limit: usize,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    pub async fn acquire(&self, host: &str) -> OwnedSemaphorePermit {
+        let semaphore = self
+            .semaphores
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limit)))
+            .clone();
+        semaphore.acquire_owned().await.unwrap()
+    }
+}
+
+/// Shared token bucket for `--rate-limit`: at most `rps` request starts per second
+/// across every worker, with no bursting so the starts are spread out evenly
+pub struct RequestRateLimiter {
+    limiter: DefaultDirectRateLimiter,
+}
+
+impl RequestRateLimiter {
+    pub fn new(rps: NonZeroU32) -> Self {
+        let quota = Quota::per_second(rps).allow_burst(NonZeroU32::MIN);
+        Self { limiter: DefaultDirectRateLimiter::direct(quota) }
+    }
+    
+    pub async fn wait(&self) {
+        self.limiter.until_ready().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    async fn max_concurrency(hosts: &[&str], limit: usize) -> usize {
+        let limiter = Arc::new(HostLimiter::new(limit));
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        
+        let mut tasks = Vec::new();
+        for host in hosts {
+            let (limiter, active, peak, host) = (limiter.clone(), active.clone(), peak.clone(), host.to_string());
+            tasks.push(tokio::spawn(async move {
+                let _permit = limiter.acquire(&host).await;
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        peak.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_same_host_serializes() {
+        assert_eq!(max_concurrency(&["a.example.com", "a.example.com"], 1).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_different_hosts_run_together() {
+        assert_eq!(max_concurrency(&["a.example.com", "b.example.com"], 1).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_spaces_out_requests() {
+        let limiter = Arc::new(RequestRateLimiter::new(NonZeroU32::new(10).unwrap()));
+        let start = std::time::Instant::now();
+        
+        let mut tasks = Vec::new();
+        for _ in 0..5 {
+            let limiter = limiter.clone();
+            tasks.push(tokio::spawn(async move { limiter.wait().await }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // first one is free, the other four wait 100ms each
+        assert!(start.elapsed() >= Duration::from_millis(380), "took {:?}", start.elapsed());
+    }
+}
@@ -0,0 +1,104 @@
+//! Tiny HTTP/1.1 server for tests. One response per connection, then close.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[allow(dead_code)] // not every test looks at every field
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Sent after the headers, before the body
+    pub body_delay: Option<Duration>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self { status, headers: Vec::new(), body: body.into(), body_delay: None }
+    }
+    
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+    
+    pub fn body_delay(mut self, delay: Duration) -> Self {
+        self.body_delay = Some(delay);
+        self
+    }
+}
+
+/// Starts serving on a random local port and returns its address.
+pub async fn serve<F>(handler: F) -> SocketAddr
+where
+    F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 1024];
+                while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut chunk).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                    }
+                }
+                
+                let head = String::from_utf8_lossy(&buf);
+                let mut lines = head.split("\r\n");
+                let mut request_line = lines.next().unwrap_or_default().split(' ');
+                let request = MockRequest {
+                    method: request_line.next().unwrap_or_default().to_string(),
+                    path: request_line.next().unwrap_or_default().to_string(),
+                    headers: lines
+                        .filter_map(|l| l.split_once(": "))
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                };
+                
+                let response = handler(&request);
+                let mut out = format!("HTTP/1.1 {} MOCK\r\n", response.status);
+                for (k, v) in &response.headers {
+                    out.push_str(&format!("{}: {}\r\n", k, v));
+                }
+                out.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len()));
+                
+                let _ = stream.write_all(out.as_bytes()).await;
+                let _ = stream.flush().await;
+                if let Some(delay) = response.body_delay {
+                    tokio::time::sleep(delay).await;
+                }
+                if request.method != "HEAD" {
+                    let _ = stream.write_all(&response.body).await;
+                }
+                let _ = stream.shutdown().await;
+            });
+        }
+    });
+    
+    addr
+}
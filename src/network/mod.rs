@@ -1,7 +1,27 @@
 pub mod checker;
+pub mod client;
 pub mod fetcher;
+pub mod dns;
+pub mod retry;
+pub mod limiter;
+pub mod adaptive;
+pub mod failure;
+pub mod chain;
+pub mod ping;
+pub mod tls;
+#[cfg(test)]
+pub(crate) mod mock;
 
 pub use checker::*;
+pub use client::*;
 pub use fetcher::*;
+pub use dns::*;
+pub use retry::*;
+pub use limiter::*;
+pub use adaptive::*;
+pub use failure::*;
+pub use chain::*;
+pub use ping::*;
+pub use tls::*;
 
 // HTTP client setup and common network utilities go here if they ever exist
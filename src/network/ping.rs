@@ -0,0 +1,103 @@
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError, ICMP};
+use crate::error::ProxyYoinkerError;
+use crate::models::{FailureKind, Node, NodeResult};
+use crate::network::dns::{DnsCache, DnsLimiter};
+use crate::network::failure::classify_io_error;
+use crate::network::retry::{retry_with_backoff, Retries};
+
+const PING_PAYLOAD: [u8; 16] = [0; 16];
+
+/// ICMP sockets for `--check-mode ping`, shared by every check. Needs a tokio runtime.
+pub struct PingClients {
+    v4: Client,
+    /// Hosts without IPv6 (or a kernel without ICMPv6 sockets) just don't get one
+    v6: Option<Client>,
+}
+
+impl PingClients {
+    /// Fails with an explanation of what to change when this process can't open ICMP sockets
+    pub fn new() -> Result<Self, ProxyYoinkerError> {
+        let v4 = Client::new(&Config::default()).map_err(|e| icmp_socket_error(&e))?;
+        let v6 = Client::new(&Config::builder().kind(ICMP::V6).build()).ok();
+        Ok(Self { v4, v6 })
+    }
+}
+
+fn icmp_socket_error(error: &io::Error) -> ProxyYoinkerError {
+    let message = if error.kind() == io::ErrorKind::PermissionDenied {
+        format!(
+            "can't open an ICMP socket ({}): run as root, grant CAP_NET_RAW \
+             (setcap cap_net_raw+ep) or allow your group in net.ipv4.ping_group_range",
+            error
+        )
+    } else {
+        format!("can't open an ICMP socket: {}", error)
+    };
+    ProxyYoinkerError::Io(io::Error::new(error.kind(), message))
+}
+
+/// One echo request per attempt, `latency` is the round trip. The port plays no part.
+pub async fn ping_check(clients: &PingClients, node: Node, timeout_duration: Duration, retries: &Retries, dns_cache: Option<&DnsCache>, dns: &DnsLimiter) -> NodeResult {
+    let ip = match node.host.parse::<IpAddr>() {
+        Ok(ip) => Some(ip),
+        Err(_) => match dns_cache {
+            Some(cache) => cache.lookup(&node.host, |host| dns.clone().resolve_host_all(host)).await.into_iter().next(),
+            None => dns.clone().resolve_host_all(node.host.clone()).await.into_iter().next(),
+        },
+    };
+    let Some(ip) = ip else {
+        return NodeResult::new(node, None, None).with_failure(FailureKind::DnsError);
+    };
+    let client = match ip {
+        IpAddr::V4(_) => &clients.v4,
+        IpAddr::V6(_) => match &clients.v6 {
+            Some(client) => client,
+            None => return NodeResult::new(node, None, None).with_failure(FailureKind::Other),
+        },
+    };
+    
+    let result = retry_with_backoff(retries, || async {
+        let mut pinger = client.pinger(ip, PingIdentifier(rand::random())).await;
+        pinger.timeout(timeout_duration);
+        match pinger.ping(PingSequence(0), &PING_PAYLOAD).await {
+            Ok((_, rtt)) => Ok(rtt.as_secs_f64() * 1000.0),
+            Err(SurgeError::Timeout { .. }) => Err(FailureKind::Timeout),
+            Err(SurgeError::IOError(e)) => Err(classify_io_error(&e)),
+            Err(_) => Err(FailureKind::Other),
+        }
+    }).await;
+    
+    match result {
+        // same as the TCP check, status 0 means "answered"
+        Ok(latency) => NodeResult::new(node, Some(0), Some(latency)),
+        Err(failure) => NodeResult::new(node, None, None).with_failure(failure),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ping_localhost_or_privilege_error() {
+        // containers and CI often can't open ICMP sockets, that has to be a clean error
+        match PingClients::new() {
+            Ok(clients) => {
+                let node = Node::new("127.0.0.1".to_string(), 443);
+                let result = ping_check(&clients, node, Duration::from_secs(2), &Retries::new(0), None, &DnsLimiter::new(1)).await;
+                assert!(result.is_working(), "{:?}", result);
+                assert!(result.latency.is_some());
+            }
+            Err(e) => assert!(e.to_string().starts_with("can't open an ICMP socket"), "{}", e),
+        }
+    }
+
+    #[test]
+    fn test_icmp_socket_error_explains_privileges() {
+        let message = icmp_socket_error(&io::Error::from(io::ErrorKind::PermissionDenied)).to_string();
+        assert!(message.contains("CAP_NET_RAW"), "{}", message);
+    }
+}
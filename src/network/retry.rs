@@ -0,0 +1,119 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tokio::time::sleep;
+use tracing::warn;
+use crate::config::RETRY_BASE_DELAY;
+
+/// `--retries` for each task, plus the `--retry-budget` they all draw from. Clones share the budget.
+#[derive(Clone, Debug, Default)]
+pub struct Retries {
+    per_task: usize,
+    budget: Option<Arc<RetryBudget>>,
+}
+
+#[derive(Debug)]
+struct RetryBudget {
+    total: usize,
+    left: AtomicUsize,
+    exhausted: AtomicBool,
+}
+
+impl Retries {
+    pub fn new(per_task: usize) -> Self {
+        Self { per_task, budget: None }
+    }
+    
+    pub fn with_budget(self, total: usize) -> Self {
+        let budget = RetryBudget { total, left: AtomicUsize::new(total), exhausted: AtomicBool::new(false) };
+        Self { budget: Some(Arc::new(budget)), ..self }
+    }
+    
+    /// Takes one retry out of the budget, false once it's gone. Warns the first time it runs dry.
+    fn take(&self) -> bool {
+        let Some(budget) = &self.budget else { return true };
+        if budget.left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1)).is_ok() {
+            return true;
+        }
+        if !budget.exhausted.swap(true, Ordering::SeqCst) {
+            warn!("⚠️  Retry budget of {} used up, nothing retries for the rest of the run", budget.total);
+        }
+        false
+    }
+}
+
+/// Runs `attempt` up to `retries + 1` times, sleeping 100ms, 200ms, 400ms, ... between
+/// failures. The first success short-circuits, otherwise the last error is returned.
+/// Each retry also needs a slot from the shared budget, if there is one.
+pub async fn retry_with_backoff<T, E, F, Fut>(retries: &Retries, mut attempt: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut delay = RETRY_BASE_DELAY;
+    let mut tries = 0;
+    
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(e) if tries >= retries.per_task || !retries.take() => return Err(e),
+            Err(_) => {
+                sleep(delay).await;
+                delay *= 2;
+                tries += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_retry_fails_twice_then_succeeds() {
+        let calls = AtomicUsize::new(0);
+        let result: Result<usize, ()> = retry_with_backoff(&Retries::new(3), || async {
+            let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if n < 3 { Err(()) } else { Ok(n) }
+        }).await;
+        
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_limit() {
+        let calls = AtomicUsize::new(0);
+        let result: Result<(), usize> = retry_with_backoff(&Retries::new(1), || async {
+            Err(calls.fetch_add(1, Ordering::SeqCst))
+        }).await;
+        
+        assert_eq!(result, Err(1));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget_shared_across_tasks() {
+        let retries = Retries::new(5).with_budget(3);
+        let calls = Arc::new(AtomicUsize::new(0));
+        
+        let tasks: Vec<_> = (0..4).map(|_| {
+            let (retries, calls) = (retries.clone(), calls.clone());
+            tokio::spawn(async move {
+                retry_with_backoff(&retries, || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err::<(), ()>(())
+                }).await
+            })
+        }).collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), Err(()));
+        }
+        
+        // one first try per task, plus the 3 retries the budget allowed in total
+        assert_eq!(calls.load(Ordering::SeqCst), 4 + 3);
+        assert!(!retries.take());
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tokio_rustls::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use tokio_rustls::TlsConnector;
+use tracing::debug;
+use crate::models::{FailureKind, Node, NodeResult};
+use crate::network::checker::connect_node;
+use crate::network::dns::{DnsCache, DnsLimiter};
+use crate::network::failure::classify_io_error;
+use crate::network::retry::{retry_with_backoff, Retries};
+use crate::parsers::proxy_urls::query_pairs;
+
+/// Takes any certificate. Plenty of nodes run self-signed ones, and `--check-mode tls` is
+/// about whether the endpoint negotiates at all. Handshake signatures are still verified.
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(&self, _: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: &ServerName<'_>, _: &[u8], _: UnixTime)
+        -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+    
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct)
+        -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+    
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct)
+        -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+    
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Connector for `--check-mode tls`, built once and shared by every check
+pub fn tls_connector() -> TlsConnector {
+    let provider = Arc::new(ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default TLS versions")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+/// Server name to send: the link's `sni=` / `peer=` / `servername=`, otherwise the host
+fn node_server_name(node: &Node) -> String {
+    node.raw_uri
+        .as_deref()
+        .and_then(|uri| {
+            query_pairs(uri)
+                .find(|(key, value)| matches!(*key, "sni" | "peer" | "servername") && !value.is_empty())
+                .map(|(_, value)| value.to_string())
+        })
+        .unwrap_or_else(|| node.host.clone())
+}
+
+/// TCP connect plus TLS handshake, `latency` covers both. A connection that goes through
+/// but doesn't negotiate is a `Tls` failure.
+pub async fn tls_check(connector: &TlsConnector, node: Node, timeout_duration: Duration, retries: &Retries, dns_cache: Option<&DnsCache>, dns: &DnsLimiter) -> NodeResult {
+    let Ok(server_name) = ServerName::try_from(node_server_name(&node)) else {
+        return NodeResult::new(node, None, None).with_failure(FailureKind::Tls);
+    };
+    
+    let result = retry_with_backoff(retries, || async {
+        let start = Instant::now();
+        let handshake = async {
+            let stream = connect_node(&node, dns_cache, dns).await.map_err(|e| classify_io_error(&e))?;
+            connector.connect(server_name.clone(), stream).await.map_err(|_| FailureKind::Tls)
+        };
+        
+        match timeout(timeout_duration, handshake).await {
+            Ok(Ok(stream)) => {
+                let version = stream.get_ref().1.protocol_version();
+                debug!("{}:{} negotiated {:?}", node.authority_host(), node.port, version);
+                Ok(start.elapsed().as_secs_f64() * 1000.0)
+            }
+            Ok(Err(failure)) => Err(failure),
+            Err(_) => Err(FailureKind::Timeout),
+        }
+    }).await;
+    
+    match result {
+        // status 0 like the TCP check, the handshake went through
+        Ok(latency) => NodeResult::new(node, Some(0), Some(latency)),
+        Err(failure) => NodeResult::new(node, None, None).with_failure(failure),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::pki_types::PrivatePkcs8KeyDer;
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    /// Local rustls server with a fresh self-signed cert for `localhost`
+    async fn tls_server() -> u16 {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certified.cert.der().clone()], key.into())
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let _ = acceptor.accept(stream).await;
+                });
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake_detected() {
+        let port = tls_server().await;
+        let node = Node::new("127.0.0.1".to_string(), port)
+            .with_raw_uri(&format!("trojan://pw@127.0.0.1:{}?sni=localhost#local", port));
+        assert_eq!(node_server_name(&node), "localhost");
+        
+        let result = tls_check(&tls_connector(), node, Duration::from_secs(2), &Retries::new(0), None, &DnsLimiter::new(1)).await;
+        assert!(result.is_working(), "{:?}", result);
+        assert!(result.latency.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_plain_tcp_is_a_tls_failure() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+            }
+        });
+        
+        let node = Node::new("127.0.0.1".to_string(), port);
+        let result = tls_check(&tls_connector(), node, Duration::from_secs(2), &Retries::new(0), None, &DnsLimiter::new(1)).await;
+        assert_eq!(result.failure, Some(FailureKind::Tls));
+    }
+}
@@ -1,20 +1,45 @@
 use serde_json::Value;
 use crate::models::{Node};
-use crate::config::{MAX_PROXIES_PER_CONFIG};
+use crate::config::{MAX_PORTS_PER_PROXY, MAX_PROXIES_PER_CONFIG};
+use crate::parsers::{node_limit, warn_truncated};
+use crate::parsers::proxy_urls::split_host_port;
+use crate::utils::{parse_port, valid_port};
 
-pub fn parse_clash_yaml(text: &str) -> Vec<Node> {
+pub fn parse_clash_yaml(text: &str, max_nodes: Option<usize>) -> Vec<Node> {
     let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_PROXIES_PER_CONFIG);
 
     if let Ok(yaml_value) = serde_yaml::from_str::<serde_yaml::Value>(text) {
         if let Some(proxies) = yaml_value.get("proxies").and_then(|v| v.as_sequence()) {
-            for proxy in proxies.iter().take(MAX_PROXIES_PER_CONFIG) {
-                if let (Some(server), Some(port)) = (
-                    proxy.get("server").and_then(|v| v.as_str()),
-                    proxy.get("port").and_then(|v| v.as_u64())
-                ) {
-                    if port <= 65535 {
-                        nodes.push(Node::new(server.to_string(), port as u16));
+            // the cap is on nodes, one proxy can hop over several ports
+            'proxies: for proxy in proxies {
+                let Some(server) = proxy.get("server").and_then(|v| v.as_str()) else {
+                    continue;
+                };
+                // hysteria & co. hop between `ports` ("443,8443-8445") instead of, or on top of, `port`
+                let mut ports: Vec<u16> = proxy.get("port").and_then(|v| v.as_u64()).and_then(valid_port).into_iter().collect();
+                if let Some(spec) = proxy.get("ports") {
+                    let spec = spec.as_str().map(str::to_string).or_else(|| spec.as_u64().map(|p| p.to_string()));
+                    for port in spec.map(|s| expand_port_spec(&s)).unwrap_or_default() {
+                        if !ports.contains(&port) {
+                            ports.push(port);
+                        }
+                    }
+                }
+                
+                for port in ports.into_iter().take(MAX_PORTS_PER_PROXY) {
+                    if nodes.len() == limit {
+                        warn_truncated("Clash YAML", limit);
+                        break 'proxies;
+                    }
+                    let mut node = Node::new(server.to_string(), port);
+                    if let Some(proxy_type) = proxy.get("type").and_then(|v| v.as_str()) {
+                        node = node.with_protocol(proxy_type);
                     }
+                    if let Some(name) = proxy.get("name").and_then(|v| v.as_str()) {
+                        node = node.with_name(name);
+                    }
+                    nodes.push(node);
                 }
             }
         }
@@ -23,8 +48,45 @@ pub fn parse_clash_yaml(text: &str) -> Vec<Node> {
     nodes
 }
 
-pub fn parse_v2ray_json(text: &str) -> Vec<Node> {
+/// `"443,8443-8445"` -> 443, 8443, 8444, 8445. Bad pieces are skipped, and a huge range
+/// only yields its first `MAX_PORTS_PER_PROXY` ports.
+fn expand_port_spec(spec: &str) -> Vec<u16> {
+    let mut ports = Vec::new();
+    for part in spec.split([',', '/']).map(str::trim) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                if let (Some(start), Some(end)) = (parse_port(start.trim()), parse_port(end.trim())) {
+                    ports.extend((start..=end).take(MAX_PORTS_PER_PROXY));
+                }
+            }
+            None => ports.extend(parse_port(part)),
+        }
+    }
+    ports
+}
+
+/// URLs of `proxy-providers` entries, these point at external proxy lists rather than nodes
+pub fn parse_clash_providers(text: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    
+    if let Ok(yaml_value) = serde_yaml::from_str::<serde_yaml::Value>(text) {
+        if let Some(providers) = yaml_value.get("proxy-providers").and_then(|v| v.as_mapping()) {
+            for provider in providers.values().take(MAX_PROXIES_PER_CONFIG) {
+                if let Some(url) = provider.get("url").and_then(|v| v.as_str()) {
+                    if url.starts_with("http://") || url.starts_with("https://") {
+                        urls.push(url.to_string());
+                    }
+                }
+            }
+        }
+    }
+    
+    urls
+}
+
+pub fn parse_v2ray_json(text: &str, max_nodes: Option<usize>) -> Vec<Node> {
     let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_PROXIES_PER_CONFIG);
     
     if let Ok(config) = serde_json::from_str::<Value>(text) {
         if let Some(outbounds) = config.get("outbounds").and_then(|v| v.as_array()) {
@@ -39,8 +101,12 @@ pub fn parse_v2ray_json(text: &str) -> Vec<Node> {
                             vnext.get("address").and_then(|v| v.as_str()),
                             vnext.get("port").and_then(|v| v.as_u64())
                         ) {
-                            if port <= 65535 {
-                                nodes.push(Node::new(address.to_string(), port as u16));
+                            if let Some(port) = valid_port(port) {
+                                let mut node = Node::new(address.to_string(), port);
+                                if let Some(protocol) = outbound.get("protocol").and_then(|v| v.as_str()) {
+                                    node = node.with_protocol(protocol);
+                                }
+                                nodes.push(node);
                             }
                         }
                     }
@@ -49,5 +115,325 @@ pub fn parse_v2ray_json(text: &str) -> Vec<Node> {
         }
     }
     
+    // vnext entries are nested, so cap after the fact
+    if nodes.len() > limit {
+        nodes.truncate(limit);
+        warn_truncated("V2Ray JSON", limit);
+    }
     nodes
 }
+
+pub fn parse_singbox_json(text: &str, max_nodes: Option<usize>) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_PROXIES_PER_CONFIG);
+    
+    if let Ok(config) = serde_json::from_str::<Value>(text) {
+        if let Some(outbounds) = config.get("outbounds").and_then(|v| v.as_array()) {
+            if outbounds.len() > limit {
+                warn_truncated("sing-box JSON", limit);
+            }
+            for outbound in outbounds.iter().take(limit) {
+                let outbound_type = outbound.get("type").and_then(|v| v.as_str()).unwrap_or("");
+                if matches!(outbound_type, "direct" | "block" | "dns") {
+                    continue;
+                }
+                if let (Some(server), Some(port)) = (
+                    outbound.get("server").and_then(|v| v.as_str()),
+                    outbound.get("server_port").and_then(|v| v.as_u64())
+                ) {
+                    if let Some(port) = valid_port(port) {
+                        nodes.push(Node::new(server.to_string(), port).with_protocol(outbound_type));
+                    }
+                }
+            }
+        }
+    }
+    
+    nodes
+}
+
+pub fn parse_sip008(text: &str, max_nodes: Option<usize>) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_PROXIES_PER_CONFIG);
+    
+    if let Ok(config) = serde_json::from_str::<Value>(text) {
+        if let Some(servers) = config.get("servers").and_then(|v| v.as_array()) {
+            if servers.len() > limit {
+                warn_truncated("SIP008", limit);
+            }
+            for server in servers.iter().take(limit) {
+                if let (Some(host), Some(port)) = (
+                    server.get("server").and_then(|v| v.as_str()),
+                    server.get("server_port").and_then(|v| v.as_u64())
+                ) {
+                    if let Some(port) = valid_port(port) {
+                        nodes.push(Node::new(host.to_string(), port).with_protocol("ss"));
+                    }
+                }
+            }
+        }
+    }
+    
+    nodes
+}
+
+/// Quantumult X `[server_local]` lines, e.g. `vmess=host:port, method=aes-128-gcm, password=..., tag=x`
+pub fn parse_quantumultx(text: &str, max_nodes: Option<usize>) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_PROXIES_PER_CONFIG);
+    let mut in_servers = false;
+    
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_servers = line.eq_ignore_ascii_case("[server_local]");
+            continue;
+        }
+        if !in_servers || line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        
+        if let Some((kind, rest)) = line.split_once('=') {
+            let hostport = rest.split(',').next().unwrap_or("").trim();
+            if let Some((host, port)) = hostport.rsplit_once(':') {
+                if let Some(port) = parse_port(port) {
+                    if nodes.len() == limit {
+                        warn_truncated("Quantumult X", limit);
+                        break;
+                    }
+                    let protocol = match kind.trim().to_lowercase().as_str() {
+                        "shadowsocks" => "ss".to_string(),
+                        other => other.to_string(),
+                    };
+                    let host = host.trim_start_matches('[').trim_end_matches(']');
+                    nodes.push(Node::new(host.to_string(), port).with_protocol(&protocol));
+                }
+            }
+        }
+    }
+    
+    nodes
+}
+
+/// Surge `[Proxy]` lines, `Name = type, host, port, key=value, ...`. Built-in `direct` /
+/// `reject` policies have no server and are skipped.
+pub fn parse_surge(text: &str, max_nodes: Option<usize>) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_PROXIES_PER_CONFIG);
+    let mut in_proxies = false;
+    
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_proxies = line.eq_ignore_ascii_case("[proxy]");
+            continue;
+        }
+        if !in_proxies || line.is_empty() || line.starts_with('#') || line.starts_with(';') || line.starts_with("//") {
+            continue;
+        }
+        
+        let Some((name, rest)) = line.split_once('=') else { continue };
+        let fields: Vec<&str> = rest.split(',').map(str::trim).collect();
+        let kind = fields[0].to_lowercase();
+        if kind == "direct" || kind.starts_with("reject") || fields.len() < 3 {
+            continue;
+        }
+        if let Some(port) = parse_port(fields[2]) {
+            if nodes.len() == limit {
+                warn_truncated("Surge", limit);
+                break;
+            }
+            let host = fields[1].trim_start_matches('[').trim_end_matches(']');
+            nodes.push(Node::new(host.to_string(), port).with_protocol(&kind).with_name(name.trim()));
+        }
+    }
+    
+    nodes
+}
+
+/// WireGuard `.conf`: every `[Peer]`'s `Endpoint = host:port` (IPv6 in brackets)
+pub fn parse_wireguard(text: &str, max_nodes: Option<usize>) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_PROXIES_PER_CONFIG);
+    
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        if !key.trim().eq_ignore_ascii_case("endpoint") {
+            continue;
+        }
+        // strip a trailing `# comment`
+        let value = value.split('#').next().unwrap_or_default().trim();
+        if let Some((host, port)) = split_host_port(value) {
+            if nodes.len() == limit {
+                warn_truncated("WireGuard", limit);
+                break;
+            }
+            nodes.push(Node::new(host.to_string(), port).with_protocol("wireguard"));
+        }
+    }
+    
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clash_port_range() {
+        let yaml = "proxies:\n  - {name: hop, type: hysteria2, server: hy.example.com, ports: \"20000-20003\"}\n";
+        let nodes = parse_clash_yaml(yaml, None);
+        let ports: Vec<u16> = nodes.iter().map(|n| n.port).collect();
+        assert_eq!(ports, vec![20000, 20001, 20002, 20003]);
+        assert!(nodes.iter().all(|n| n.name.as_deref() == Some("hop") && n.protocol.as_deref() == Some("hysteria2")));
+        
+        // a hopping range over half the port space gets capped
+        let yaml = "proxies:\n  - {name: wide, type: hysteria, server: hy.example.com, ports: \"1000-60000\"}\n";
+        assert_eq!(parse_clash_yaml(yaml, None).len(), MAX_PORTS_PER_PROXY);
+    }
+
+    #[test]
+    fn test_parse_clash_port_list() {
+        let yaml = "proxies:\n  - {name: \"HK 01\", type: hysteria2, server: hk.example.com, port: 443, ports: \"443,8443-8445, 0, nope\"}\n";
+        let nodes = parse_clash_yaml(yaml, None);
+        let ports: Vec<u16> = nodes.iter().map(|n| n.port).collect();
+        assert_eq!(ports, vec![443, 8443, 8444, 8445]);
+        assert_eq!(nodes[0].name.as_deref(), Some("HK 01"));
+    }
+
+    #[test]
+    fn test_parse_clash_limit_counts_ports() {
+        let yaml = "proxies:\n  - {name: a, type: hysteria2, server: a.example.com, ports: \"1000-1009\"}\n  - {name: b, type: ss, server: b.example.com, port: 8388}\n";
+        let nodes = parse_clash_yaml(yaml, Some(4));
+        let ports: Vec<u16> = nodes.iter().map(|n| n.port).collect();
+        assert_eq!(ports, vec![1000, 1001, 1002, 1003]);
+    }
+
+    #[test]
+    fn test_line_based_parsers_honour_max_nodes() {
+        let qx = "[server_local]\nvmess=a.example.com:443, tag=a\ntrojan=b.example.com:443, tag=b\nss=c.example.com:8388, tag=c\n";
+        let surge = "[Proxy]\nA = ss, a.example.com, 8388\nB = trojan, b.example.com, 443\nC = vmess, c.example.com, 443\n";
+        let wg = "[Peer]\nEndpoint = a.example.com:51820\n[Peer]\nEndpoint = b.example.com:51820\n[Peer]\nEndpoint = c.example.com:51820\n";
+        for nodes in [parse_quantumultx(qx, Some(2)), parse_surge(surge, Some(2)), parse_wireguard(wg, Some(2))] {
+            let hosts: Vec<&str> = nodes.iter().map(|n| n.host.as_str()).collect();
+            assert_eq!(hosts, vec!["a.example.com", "b.example.com"]);
+        }
+        assert_eq!(parse_wireguard(wg, Some(0)).len(), 3);
+    }
+
+    #[test]
+    fn test_parse_wireguard_ipv4() {
+        let conf = "[Interface]\nPrivateKey = abc=\nAddress = 10.0.0.2/32\n\n[Peer]\nPublicKey = def=\nAllowedIPs = 0.0.0.0/0\nEndpoint = 203.0.113.7:51820\n";
+        let nodes = parse_wireguard(conf, None);
+        assert_eq!(nodes, vec![Node::new("203.0.113.7".to_string(), 51820)]);
+        assert_eq!(nodes[0].protocol.as_deref(), Some("wireguard"));
+    }
+
+    #[test]
+    fn test_parse_wireguard_ipv6() {
+        let conf = "[Peer]\nPublicKey = def=\nendpoint=[2001:db8::7]:51821 # home\n[Peer]\nEndpoint = wg.example.com:443\n";
+        assert_eq!(parse_wireguard(conf, None), vec![
+            Node::new("2001:db8::7".to_string(), 51821),
+            Node::new("wg.example.com".to_string(), 443),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_clash_providers() {
+        let text = r#"
+proxies:
+  - {name: inline, type: trojan, server: inline.example.com, port: 443, password: x}
+proxy-providers:
+  remote:
+    type: http
+    url: "https://provider.example.com/list.yaml?token=abc"
+    path: ./providers/remote.yaml
+    interval: 3600
+  local:
+    type: file
+    path: ./providers/local.yaml
+"#;
+        assert_eq!(parse_clash_yaml(text, None), vec![Node::new("inline.example.com".to_string(), 443)]);
+        assert_eq!(parse_clash_providers(text), vec!["https://provider.example.com/list.yaml?token=abc"]);
+    }
+
+    #[test]
+    fn test_parse_singbox_json() {
+        let text = r#"{
+            "outbounds": [
+                {"type": "trojan", "tag": "tj", "server": "tj.example.com", "server_port": 443, "password": "x"},
+                {"type": "vmess", "tag": "vm", "server": "vm.example.com", "server_port": 8443, "uuid": "y"},
+                {"type": "direct", "tag": "direct"},
+                {"type": "dns", "tag": "dns-out"}
+            ]
+        }"#;
+        let nodes = parse_singbox_json(text, None);
+        assert_eq!(nodes, vec![
+            Node::new("tj.example.com".to_string(), 443),
+            Node::new("vm.example.com".to_string(), 8443),
+        ]);
+        assert_eq!(nodes[0].protocol.as_deref(), Some("trojan"));
+        assert_eq!(nodes[1].protocol.as_deref(), Some("vmess"));
+        assert!(parse_v2ray_json(text, None).is_empty());
+    }
+
+    #[test]
+    fn test_parse_sip008() {
+        let text = r#"{
+            "version": 1,
+            "servers": [
+                {"id": "1", "remarks": "one", "server": "ss1.example.com", "server_port": 8388, "password": "x", "method": "aes-256-gcm"},
+                {"id": "2", "remarks": "two", "server": "203.0.113.5", "server_port": 443, "password": "y", "method": "chacha20-ietf-poly1305"}
+            ]
+        }"#;
+        let nodes = parse_sip008(text, None);
+        assert_eq!(nodes, vec![
+            Node::new("ss1.example.com".to_string(), 8388),
+            Node::new("203.0.113.5".to_string(), 443),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_quantumultx_vmess() {
+        let text = "[general]\nserver_check_url=http://example.com:80/\n\n[server_local]\n\
+                    vmess=vm.example.com:443, method=chacha20-poly1305, password=uuid, obfs=wss, tag=vm\n\
+                    ; vmess=commented.example.com:443, method=none\n\n[filter_local]\nhost, other.example.com:80, proxy\n";
+        let nodes = parse_quantumultx(text, None);
+        assert_eq!(nodes, vec![Node::new("vm.example.com".to_string(), 443)]);
+        assert_eq!(nodes[0].protocol.as_deref(), Some("vmess"));
+    }
+
+    #[test]
+    fn test_parse_surge_proxies() {
+        let text = "[General]\nloglevel = notify\n\n[Proxy]\n\
+                    On Direct = direct\n\
+                    Block = reject\n\
+                    HK 01 = ss, hk.example.com, 8388, encrypt-method=aes-128-gcm, password=pw\n\
+                    # Old = trojan, old.example.com, 443, password=pw\n\
+                    JP = trojan, 203.0.113.7, 443, password=pw, sni=jp.example.com\n\n\
+                    [Proxy Group]\nAuto = url-test, HK 01, JP\n";
+        let nodes = parse_surge(text, None);
+        assert_eq!(nodes, vec![
+            Node::new("hk.example.com".to_string(), 8388),
+            Node::new("203.0.113.7".to_string(), 443),
+        ]);
+        assert_eq!(nodes[0].name.as_deref(), Some("HK 01"));
+        assert_eq!(nodes[0].protocol.as_deref(), Some("ss"));
+        assert_eq!(nodes[1].name.as_deref(), Some("JP"));
+        assert_eq!(nodes[1].protocol.as_deref(), Some("trojan"));
+    }
+
+    #[test]
+    fn test_parse_quantumultx_trojan_and_ss() {
+        let text = "[server_local]\n\
+                    trojan=tj.example.com:443, password=pw, over-tls=true, tls-host=tj.example.com, tag=tj\n\
+                    shadowsocks=1.2.3.4:8388, method=aes-256-gcm, password=pw, tag=ss\n";
+        let nodes = parse_quantumultx(text, None);
+        assert_eq!(nodes, vec![
+            Node::new("tj.example.com".to_string(), 443),
+            Node::new("1.2.3.4".to_string(), 8388),
+        ]);
+        assert_eq!(nodes[0].protocol.as_deref(), Some("trojan"));
+        assert_eq!(nodes[1].protocol.as_deref(), Some("ss"));
+    }
+}
@@ -1,34 +1,184 @@
+use std::collections::VecDeque;
+use std::net::Ipv6Addr;
+use std::str::CharIndices;
 use serde_json::Value;
 use crate::models::{Node, RegexPatterns};
-use crate::config::{MAX_JSON_MATCHES, MAX_HOSTPORT_MATCHES};
+use crate::config::{MAX_JSON_DEPTH, MAX_JSON_MATCHES, MAX_HOSTPORT_MATCHES};
+use crate::parsers::{node_limit, warn_truncated};
+use crate::utils::{parse_port, valid_port};
 
-pub fn parse_inline_json(text: &str, patterns: &RegexPatterns) -> Vec<Node> {
+/// JSON proxy objects embedded anywhere in the text (YAML list items, arrays, nested configs).
+/// Objects are cut out with a brace-balanced scan, so nested `{}` don't truncate them.
+pub fn parse_inline_json(text: &str, max_nodes: Option<usize>) -> Vec<Node> {
     let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_JSON_MATCHES);
     
-    for cap in patterns.json_inline_regex.captures_iter(text).take(MAX_JSON_MATCHES) {
-        if let Some(json_str) = cap.get(1) {
-            if let Ok(obj) = serde_json::from_str::<Value>(json_str.as_str()) {
-                if let (Some(host), Some(port)) = (
-                    obj.get("server").or_else(|| obj.get("address")).and_then(|v| v.as_str()),
-                    obj.get("port").and_then(|v| v.as_u64())
-                ) {
-                    if port <= 65535 {
-                        nodes.push(Node::new(host.to_string(), port as u16));
+    for value in json_objects(text) {
+        collect_json_nodes(&value, &mut nodes);
+        if nodes.len() >= limit {
+            warn_truncated("inline JSON", limit);
+            nodes.truncate(limit);
+            break;
+        }
+    }
+    
+    nodes
+}
+
+/// Every top-level JSON object in `text`, produced as the scan reaches it so a caller that
+/// has enough can stop early. One pass over the text: open braces go on a stack, and each
+/// span that closes is kept with the spans it closed inside it. An outermost span is
+/// parsed when it closes; if it isn't valid JSON, or sits inside a `{` that never closes,
+/// the objects inside it are tried instead.
+fn json_objects(text: &str) -> JsonObjects<'_> {
+    JsonObjects {
+        text,
+        chars: text.char_indices(),
+        open: Vec::new(),
+        in_string: false,
+        escaped: false,
+        ready: VecDeque::new(),
+        done: false,
+    }
+}
+
+/// A closed `{...}` and the closed spans directly inside it
+struct Span {
+    start: usize,
+    end: usize,
+    children: Vec<Span>,
+}
+
+struct JsonObjects<'a> {
+    text: &'a str,
+    chars: CharIndices<'a>,
+    /// Offset of every `{` still open, with the spans closed inside it so far
+    open: Vec<(usize, Vec<Span>)>,
+    in_string: bool,
+    escaped: bool,
+    ready: VecDeque<Value>,
+    done: bool,
+}
+
+impl Iterator for JsonObjects<'_> {
+    type Item = Value;
+    
+    fn next(&mut self) -> Option<Value> {
+        loop {
+            if let Some(value) = self.ready.pop_front() {
+                return Some(value);
+            }
+            if self.done {
+                return None;
+            }
+            let Some((i, c)) = self.chars.next() else {
+                // braces that never closed: whatever closed inside them still counts
+                self.done = true;
+                for (_, children) in std::mem::take(&mut self.open) {
+                    for child in children {
+                        settle(self.text, child, 0, &mut self.ready);
                     }
                 }
+                continue;
+            };
+            
+            if self.in_string {
+                match c {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    // JSON strings can't hold a raw newline, so a stray quote ends with its line
+                    '"' | '\n' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' if !self.open.is_empty() => self.in_string = true,
+                '{' => self.open.push((i, Vec::new())),
+                '}' => {
+                    let Some((start, children)) = self.open.pop() else { continue };
+                    let span = Span { start, end: i + 1, children };
+                    match self.open.last_mut() {
+                        Some((_, siblings)) => siblings.push(span),
+                        None => settle(self.text, span, 0, &mut self.ready),
+                    }
+                }
+                _ => {}
             }
         }
     }
-    
-    nodes
 }
 
-pub fn parse_generic(text: &str, patterns: &RegexPatterns) -> Vec<Node> {
+/// Parses `span`, falling back to the spans inside it when it isn't valid JSON.
+/// Stops going down past `MAX_JSON_DEPTH`, where serde_json gives up anyway.
+fn settle(text: &str, span: Span, depth: usize, ready: &mut VecDeque<Value>) {
+    match serde_json::from_str::<Value>(&text[span.start..span.end]) {
+        Ok(value) => ready.push_back(value),
+        Err(_) if depth < MAX_JSON_DEPTH => {
+            for child in span.children {
+                settle(text, child, depth + 1, ready);
+            }
+        }
+        Err(_) => {}
+    }
+}
+
+/// An object with `server`/`address` and `port` is a node, anything else gets searched for them
+fn collect_json_nodes(value: &Value, nodes: &mut Vec<Node>) {
+    match value {
+        Value::Object(obj) => {
+            let host = obj.get("server").or_else(|| obj.get("address")).and_then(|v| v.as_str());
+            let port = obj.get("port").and_then(|v| v.as_u64());
+            if let (Some(host), Some(port)) = (host, port) {
+                if let Some(port) = valid_port(port) {
+                    let mut node = Node::new(host.to_string(), port);
+                    if let Some(proxy_type) = obj.get("type").and_then(|v| v.as_str()) {
+                        node = node.with_protocol(proxy_type);
+                    }
+                    nodes.push(node);
+                }
+                return;
+            }
+            obj.values().for_each(|v| collect_json_nodes(v, nodes));
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_json_nodes(v, nodes)),
+        _ => {}
+    }
+}
+
+pub fn parse_generic(text: &str, patterns: &RegexPatterns, max_nodes: Option<usize>) -> Vec<Node> {
     let mut nodes = Vec::new();
+    let limit = node_limit(max_nodes, MAX_HOSTPORT_MATCHES);
     
-    for cap in patterns.hostport_regex.captures_iter(text).take(MAX_HOSTPORT_MATCHES) {
+    // Bracketed IPv6 first, e.g. [2001:db8::1]:443
+    for cap in patterns.ipv6_hostport_regex.captures_iter(text) {
+        if nodes.len() >= limit {
+            warn_truncated("generic", limit);
+            return nodes;
+        }
+        // zone ids (fe80::1%eth0) are link-local and useless outside the box that wrote them
+        if cap.get(2).is_some() {
+            continue;
+        }
+        if let (Some(host), Some(port_str)) = (cap.get(1), cap.get(3)) {
+            if host.as_str().parse::<Ipv6Addr>().is_ok() {
+                if let Some(port) = parse_port(port_str.as_str()) {
+                    nodes.push(Node::new(host.as_str().to_string(), port));
+                }
+            }
+        }
+    }
+    
+    // Blank the IPv6 matches out so the hostname pass doesn't split them into junk like 2001:4860
+    let text = patterns.ipv6_hostport_regex.replace_all(text, " ");
+    
+    for cap in patterns.hostport_regex.captures_iter(&text) {
+        if nodes.len() >= limit {
+            warn_truncated("generic", limit);
+            break;
+        }
         if let (Some(host), Some(port_str)) = (cap.get(1), cap.get(2)) {
-            if let Ok(port) = port_str.as_str().parse::<u16>() {
+            if let Some(port) = parse_port(port_str.as_str()) {
                 nodes.push(Node::new(host.as_str().to_string(), port));
             }
         }
@@ -36,3 +186,88 @@ pub fn parse_generic(text: &str, patterns: &RegexPatterns) -> Vec<Node> {
     
     nodes
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inline_json_nested_objects() {
+        // `[^}]*` stopped at the first inner `}`, cutting these objects in half
+        let text = r#"proxies:
+  - {"name": "a", "type": "vmess", "server": "a.example.com", "port": 443, "ws-opts": {"path": "/ws", "headers": {"Host": "cdn.example.com"}}}
+  - {"name": "b {weird}", "type": "trojan", "server": "b.example.com", "port": 8443, "tls": {"sni": "b.example.com"}}
+"#;
+        let nodes = parse_inline_json(text, None);
+        assert_eq!(nodes, vec![
+            Node::new("a.example.com".to_string(), 443),
+            Node::new("b.example.com".to_string(), 8443),
+        ]);
+        assert_eq!(nodes[0].protocol.as_deref(), Some("vmess"));
+    }
+
+    #[test]
+    fn test_parse_inline_json_array_in_wrapper() {
+        let text = r#"{"data": {"nodes": [{"address": "c.example.com", "port": 1080, "extra": {"a": 1}}, {"server": "d.example.com", "port": 0}]}}"#;
+        assert_eq!(parse_inline_json(text, None), vec![Node::new("c.example.com".to_string(), 1080)]);
+    }
+
+    #[test]
+    fn test_parse_inline_json_after_stray_brace() {
+        // the `{` in the comment never closes, the objects after it still count
+        let text = r#"# see {docs
+  - {"server": "e.example.com", "port": 443}
+  - {"server": "f.example.com", "port": 8443}
+"#;
+        assert_eq!(parse_inline_json(text, None), vec![
+            Node::new("e.example.com".to_string(), 443),
+            Node::new("f.example.com".to_string(), 8443),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_inline_json_unclosed_braces_and_quotes() {
+        // every `{` and `"` here is left open; rescanning from each one took quadratic time
+        let mut text = "{ \"\n".repeat(200_000);
+        text.push_str(r#"{"server": "g.example.com", "port": 443} and {"server": "h.example.com", "port": 8443}"#);
+        let start = std::time::Instant::now();
+        assert_eq!(parse_inline_json(&text, None), vec![
+            Node::new("g.example.com".to_string(), 443),
+            Node::new("h.example.com".to_string(), 8443),
+        ]);
+        assert!(start.elapsed() < std::time::Duration::from_secs(2), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_json_objects_is_lazy() {
+        let text = r#"{"server": "a.example.com", "port": 1} {"server": "b.example.com", "port": 2} {"#;
+        let mut objects = json_objects(text);
+        assert_eq!(objects.next().unwrap()["server"], "a.example.com");
+        // the second object is still unread
+        assert!(objects.ready.is_empty() && !objects.done);
+    }
+
+    #[test]
+    fn test_parse_generic_ipv6_literal() {
+        let patterns = RegexPatterns::new();
+        let nodes = parse_generic("server [2001:4860:4860::8888]:443 here", &patterns, None);
+        assert_eq!(nodes, vec![Node::new("2001:4860:4860::8888".to_string(), 443)]);
+    }
+
+    #[test]
+    fn test_parse_generic_ipv6_zone_id_skipped() {
+        let patterns = RegexPatterns::new();
+        let nodes = parse_generic("[fe80::1%eth0]:8080", &patterns, None);
+        assert!(nodes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_generic_hostname() {
+        let patterns = RegexPatterns::new();
+        let nodes = parse_generic("example.com:8443 and 1.2.3.4:80", &patterns, None);
+        assert_eq!(nodes, vec![
+            Node::new("example.com".to_string(), 8443),
+            Node::new("1.2.3.4".to_string(), 80),
+        ]);
+    }
+}
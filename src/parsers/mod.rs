@@ -7,106 +7,451 @@ pub mod generic;
 
 // Your detect_format_and_parse and parse_subscription_safe functions go here
 use crate::models::{Node, RegexPatterns};
-use crate::utils::{safe_limit_text};
+use crate::utils::{decode_flexible, safe_limit_text};
 use crate::parsers::{
-    proxy_urls::{parse_vmess, parse_protocol_url, parse_ssr},
-    config_files::{parse_clash_yaml, parse_v2ray_json},
+    proxy_urls::{parse_vmess, parse_protocol_url, parse_ssr, parse_ssd, parse_socks, parse_hysteria},
+    config_files::{parse_clash_yaml, parse_v2ray_json, parse_singbox_json, parse_sip008, parse_quantumultx, parse_surge, parse_wireguard},
     generic::{parse_generic, parse_inline_json},
 };
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::time::{Instant, timeout};
-use crate::config::{PARSE_TIMEOUT};
+use tracing::{debug, warn};
 
-pub fn detect_format_and_parse(text: &str, patterns: &RegexPatterns, verbose: bool) -> Vec<Node> {
+/// `--max-nodes-per-sub`: `None` keeps the parser's own cap, `Some(0)` means unlimited
+pub(crate) fn node_limit(max_nodes: Option<usize>, default: usize) -> usize {
+    match max_nodes {
+        None => default,
+        Some(0) => usize::MAX,
+        Some(n) => n,
+    }
+}
+
+pub(crate) fn warn_truncated(parser: &str, limit: usize) {
+    warn!("{} parser stopped at {} nodes, the rest of the subscription was dropped (see --max-nodes-per-sub)", parser, limit);
+}
+
+/// Empty `protocols` means everything is allowed
+fn protocol_allowed(protocols: &[String], protocol: &str) -> bool {
+    protocols.is_empty() || protocols.iter().any(|p| p == protocol)
+}
+
+/// A parser that was tried on a body and came up empty, and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseAttempt {
+    pub parser: &'static str,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ParseAttempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.parser, self.reason)
+    }
+}
+
+fn no_nodes(attempts: &mut Vec<ParseAttempt>, parser: &'static str, reason: impl Into<String>) {
+    let reason = reason.into();
+    debug!("{} parser found no nodes ({})", parser, reason);
+    attempts.push(ParseAttempt { parser, reason });
+}
+
+/// Returns the nodes plus a label naming the parser that produced them (`clash-yaml`, `vmess`, `generic`, ...)
+pub fn detect_format_and_parse(text: &str, patterns: &RegexPatterns, protocols: &[String], max_nodes: Option<usize>) -> (Vec<Node>, &'static str) {
+    let (nodes, format, _) = detect_format_and_parse_traced(text, patterns, protocols, max_nodes);
+    (nodes, format)
+}
+
+/// `detect_format_and_parse`, plus every parser that was tried before the one that won
+pub fn detect_format_and_parse_traced(text: &str, patterns: &RegexPatterns, protocols: &[String], max_nodes: Option<usize>) -> (Vec<Node>, &'static str, Vec<ParseAttempt>) {
+    let mut attempts = Vec::new();
+    let (mut nodes, format) = detect_and_parse(text, patterns, protocols, max_nodes, &mut attempts);
+    
+    // Generic/JSON sources don't go through the per-protocol branches, so filter on the tags too
+    if !protocols.is_empty() {
+        let before = nodes.len();
+        nodes.retain(|n| n.protocol.as_deref().is_some_and(|p| protocol_allowed(protocols, p)));
+        if before > 0 && nodes.is_empty() {
+            no_nodes(&mut attempts, format, format!("all {} nodes dropped by --protocols", before));
+        }
+    }
+    
+    (nodes, format, attempts)
+}
+
+fn detect_and_parse(text: &str, patterns: &RegexPatterns, protocols: &[String], max_nodes: Option<usize>, attempts: &mut Vec<ParseAttempt>) -> (Vec<Node>, &'static str) {
     if text.trim().is_empty() {
-        return Vec::new();
+        no_nodes(attempts, "empty", "body is blank");
+        return (Vec::new(), "empty");
     }
     
     let text = safe_limit_text(text);
     let text_lower = text.to_lowercase();
     
-    if verbose {
-        println!("VERBOSE: Detecting format for {} chars", text.len());
+    debug!("Detecting format for {} chars", text.len());
+    
+    // Whole-body base64 (common for vmess lists and some Clash endpoints): detect again on the decoded text
+    if let Some(decoded) = decode_whole_body(&text) {
+        debug!("Body is base64, detecting again on {} decoded chars", decoded.len());
+        let (nodes, format) = detect_and_parse(&decoded, patterns, protocols, max_nodes, attempts);
+        if !nodes.is_empty() { return (nodes, format); }
+        no_nodes(attempts, "base64", format!("decoded {} chars but nothing parsed from them", decoded.len()));
     }
     
     // Try format-specific parsers
     if text_lower.contains("proxies:") || text_lower.contains("proxy-groups:") {
-        if verbose { println!("VERBOSE: Trying Clash YAML parser"); }
-        let nodes = parse_clash_yaml(&text);
-        if !nodes.is_empty() { return nodes; }
+        debug!("Trying Clash YAML parser");
+        let nodes = parse_clash_yaml(&text, max_nodes);
+        if !nodes.is_empty() { return (nodes, "clash-yaml"); }
+        no_nodes(attempts, "clash-yaml", clash_yaml_problem(&text));
+    }
+    
+    if text_lower.contains("[server_local]") {
+        debug!("Trying Quantumult X parser");
+        let nodes = parse_quantumultx(&text, max_nodes);
+        if !nodes.is_empty() { return (nodes, "quantumultx"); }
+        no_nodes(attempts, "quantumultx", "no server_local entry with host:port");
+    }
+    
+    if text.contains("[Proxy]") {
+        debug!("Trying Surge parser");
+        let nodes = parse_surge(&text, max_nodes);
+        if !nodes.is_empty() { return (nodes, "surge"); }
+        no_nodes(attempts, "surge", "no [Proxy] line with type, host, port");
+    }
+    
+    if (text.contains("[Interface]") || text.contains("[Peer]")) && protocol_allowed(protocols, "wireguard") {
+        debug!("Trying WireGuard parser");
+        let nodes = parse_wireguard(&text, max_nodes);
+        if !nodes.is_empty() { return (nodes, "wireguard"); }
+        no_nodes(attempts, "wireguard", "no Endpoint = host:port line");
+    }
+    
+    if text.trim_start().starts_with('{') && text.contains("\"servers\"") {
+        debug!("Trying SIP008 parser");
+        let nodes = parse_sip008(&text, max_nodes);
+        if !nodes.is_empty() { return (nodes, "sip008"); }
+        no_nodes(attempts, "sip008", "no server with server/server_port");
+    }
+    
+    // Hysteria v1 client configs carry the whole endpoint in one "server": "host:port"
+    if text.trim_start().starts_with('{') && text.contains("\"server\"") && text_lower.contains("_mbps\"")
+        && protocol_allowed(protocols, "hysteria") {
+        debug!("Trying Hysteria JSON parser");
+        let nodes = parse_hysteria(&text, patterns);
+        if !nodes.is_empty() { return (nodes, "hysteria-json"); }
+        no_nodes(attempts, "hysteria-json", "no server string with host:port");
     }
     
     if text.trim_start().starts_with('{') && (text_lower.contains("outbounds") || text_lower.contains("inbounds")) {
-        if verbose { println!("VERBOSE: Trying V2Ray JSON parser"); }
-        let nodes = parse_v2ray_json(&text);
-        if !nodes.is_empty() { return nodes; }
+        debug!("Trying V2Ray JSON parser");
+        let nodes = parse_v2ray_json(&text, max_nodes);
+        if !nodes.is_empty() { return (nodes, "v2ray-json"); }
+        no_nodes(attempts, "v2ray-json", "no outbound with address/port");
+        
+        if text_lower.contains("outbounds") {
+            debug!("Trying sing-box JSON parser");
+            let nodes = parse_singbox_json(&text, max_nodes);
+            if !nodes.is_empty() { return (nodes, "singbox-json"); }
+            no_nodes(attempts, "singbox-json", "no outbound with server/server_port");
+        }
     }
     
-    if text.contains("vmess://") {
-        if verbose { println!("VERBOSE: Trying VMess parser"); }
+    if text.contains("vmess://") && protocol_allowed(protocols, "vmess") {
+        debug!("Trying VMess parser");
         let nodes = parse_vmess(&text, patterns);
-        if !nodes.is_empty() { return nodes; }
+        if !nodes.is_empty() { return (nodes, "vmess"); }
+        no_nodes(attempts, "vmess", "no link decoded to a host and port");
     }
     
-    for protocol in &["vless", "trojan", "ss"] {
-        if text.contains(&format!("{}://", protocol)) {
-            if verbose { println!("VERBOSE: Trying {} parser", protocol); }
+    for protocol in ["vless", "trojan", "ss"] {
+        // Trojan-Go links share the trojan format
+        let present = text.contains(&format!("{}://", protocol)) || (protocol == "trojan" && text.contains("trojan-go://"));
+        if present && protocol_allowed(protocols, protocol) {
+            debug!("Trying {} parser", protocol);
             let nodes = parse_protocol_url(&text, patterns, protocol);
-            if !nodes.is_empty() { return nodes; }
+            if !nodes.is_empty() { return (nodes, protocol); }
+            no_nodes(attempts, protocol, "no link with host:port");
         }
     }
     
-    if text.contains("ssr://") {
-        if verbose { println!("VERBOSE: Trying SSR parser"); }
+    if text.contains("ssr://") && protocol_allowed(protocols, "ssr") {
+        debug!("Trying SSR parser");
         let nodes = parse_ssr(&text, patterns);
-        if !nodes.is_empty() { return nodes; }
+        if !nodes.is_empty() { return (nodes, "ssr"); }
+        no_nodes(attempts, "ssr", "no link decoded to host:port");
+    }
+    
+    if text.contains("ssd://") && protocol_allowed(protocols, "ss") {
+        debug!("Trying ShadowsocksD parser");
+        let nodes = parse_ssd(&text, patterns);
+        if !nodes.is_empty() { return (nodes, "ssd"); }
+        no_nodes(attempts, "ssd", "no server with host/port");
+    }
+    
+    if (text.contains("socks://") || text.contains("socks5://")) && protocol_allowed(protocols, "socks5") {
+        debug!("Trying SOCKS parser");
+        let nodes = parse_socks(&text, patterns);
+        if !nodes.is_empty() { return (nodes, "socks5"); }
+        no_nodes(attempts, "socks5", "no link with host:port");
+    }
+    
+    if text.contains("naive+") && protocol_allowed(protocols, "naive") {
+        debug!("Trying NaiveProxy parser");
+        let nodes = parse_protocol_url(&text, patterns, "naive");
+        if !nodes.is_empty() { return (nodes, "naive"); }
+        no_nodes(attempts, "naive", "no link with host:port");
+    }
+    
+    if text.contains("hysteria://") && protocol_allowed(protocols, "hysteria") {
+        debug!("Trying Hysteria parser");
+        let nodes = parse_hysteria(&text, patterns);
+        if !nodes.is_empty() { return (nodes, "hysteria"); }
+        no_nodes(attempts, "hysteria", "no link with host:port");
     }
     
     if text.contains('{') && (text_lower.contains("server") || text_lower.contains("address")) {
-        if verbose { println!("VERBOSE: Trying inline JSON parser"); }
-        let nodes = parse_inline_json(&text, patterns);
-        if !nodes.is_empty() { return nodes; }
+        debug!("Trying inline JSON parser");
+        let nodes = parse_inline_json(&text, max_nodes);
+        if !nodes.is_empty() { return (nodes, "inline-json"); }
+        no_nodes(attempts, "inline-json", "no JSON object with server/address and port");
     }
     
-    if verbose { println!("VERBOSE: Using generic parser"); }
-    parse_generic(&text, patterns)
+    debug!("Using generic parser");
+    let nodes = parse_generic(&text, patterns, max_nodes);
+    if nodes.is_empty() {
+        let trimmed = text_lower.trim_start();
+        if trimmed.starts_with("<!doctype html") || trimmed.starts_with("<html") {
+            no_nodes(attempts, "generic", "0 host:port matches, body is an HTML page (login wall or error page?)");
+        } else {
+            no_nodes(attempts, "generic", "0 host:port matches");
+        }
+    }
+    (nodes, "generic")
+}
+
+/// Why the Clash YAML parser found nothing
+fn clash_yaml_problem(text: &str) -> String {
+    match serde_yaml::from_str::<serde_yaml::Value>(text) {
+        Err(e) => format!("invalid YAML ({})", e),
+        Ok(doc) => match doc.get("proxies").map(|p| p.as_sequence()) {
+            None => "no proxies key".to_string(),
+            Some(None) => "proxies isn't a list".to_string(),
+            Some(Some(list)) if list.is_empty() => "proxies list is empty".to_string(),
+            Some(Some(_)) => "no proxy with server and a valid port".to_string(),
+        },
+    }
+}
+
+/// The body as text if the whole thing (newlines aside) is one base64 blob
+fn decode_whole_body(text: &str) -> Option<String> {
+    let compact: String = text.split_whitespace().collect();
+    let is_base64 = |c: char| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '-' | '_');
+    if compact.len() < 16 || !compact.chars().all(is_base64) {
+        return None;
+    }
+    String::from_utf8(decode_flexible(&compact)?).ok()
 }
 
 pub async fn parse_subscription_safe(
     url: String,
     body: String,
-    patterns: &RegexPatterns,
-    verbose: bool,
-) -> (String, Vec<Node>) {
+    patterns: Arc<RegexPatterns>,
+    parse_timeout: Duration,
+    protocols: Arc<Vec<String>>,
+    max_nodes: Option<usize>,
+) -> SubscriptionParse {
     let start = Instant::now();
+    let body_bytes = body.len();
+    let mut attempts = Vec::new();
     
     if body.is_empty() {
-        if verbose {
-            println!("VERBOSE: {} - No body to parse", url);
-        }
-        return (url, Vec::new());
+        debug!("{} - No body to parse", url);
+        no_nodes(&mut attempts, "empty", "no body");
+        return SubscriptionParse { url, body_bytes, nodes: Vec::new(), format: "empty", attempts };
     }
     
     if body.len() > 100 * 1024 * 1024 {
-        println!("Skipping {} - too large ({} bytes)", url, body.len());
-        return (url, Vec::new());
+        warn!("Skipping {} - too large ({} bytes)", url, body.len());
+        no_nodes(&mut attempts, "too-large", "body over 100MB, not parsed");
+        return SubscriptionParse { url, body_bytes, nodes: Vec::new(), format: "too-large", attempts };
     }
     
-    let result = timeout(PARSE_TIMEOUT, async {
-        detect_format_and_parse(&body, patterns, verbose)
-    }).await;
+    // parsing never yields, so it runs on the blocking pool where the timeout can cut in.
+    // A timed-out parse finishes in the background and its nodes are dropped.
+    let parse = tokio::task::spawn_blocking(move || detect_format_and_parse_traced(&body, &patterns, &protocols, max_nodes));
+    let result = timeout(parse_timeout, parse).await;
     
-    let nodes = match result {
-        Ok(nodes) => nodes,
+    let (nodes, format, attempts) = match result {
+        Ok(Ok(parsed)) => parsed,
+        Ok(Err(e)) => {
+            warn!("⚠️  Parser crashed on {}: {}", url, e);
+            no_nodes(&mut attempts, "crashed", e.to_string());
+            (Vec::new(), "crashed", attempts)
+        }
         Err(_) => {
-            println!("Parse timeout for {} - skipping", url);
-            Vec::new()
+            warn!("Parse timeout for {} - skipping", url);
+            no_nodes(&mut attempts, "timeout", format!("parsing took over {:.0}s", parse_timeout.as_secs_f64()));
+            (Vec::new(), "timeout", attempts)
         }
     };
     
     let elapsed = start.elapsed().as_secs_f64();
-    if verbose {
-        println!("VERBOSE: {} - Parse complete, found {} nodes in {:.1}s", url, nodes.len(), elapsed);
-    }
+    debug!("{} - Parse complete, found {} nodes in {:.1}s", url, nodes.len(), elapsed);
     
-    (url, nodes)
+    SubscriptionParse { url, body_bytes, nodes, format, attempts }
+}
+
+/// What parsing one subscription produced, with enough detail for `--diagnostics`
+#[derive(Debug, Clone)]
+pub struct SubscriptionParse {
+    pub url: String,
+    pub body_bytes: usize,
+    pub nodes: Vec<Node>,
+    pub format: &'static str,
+    pub attempts: Vec<ParseAttempt>,
+}
+
+impl SubscriptionParse {
+    /// `url | 1234 bytes | generic -> 0 nodes | clash-yaml: no proxies key; generic: 0 host:port matches`
+    pub fn diagnostic_line(&self) -> String {
+        let attempts: Vec<String> = self.attempts.iter().map(ToString::to_string).collect();
+        let attempts = if attempts.is_empty() { "—".to_string() } else { attempts.join("; ") };
+        format!("{} | {} bytes | {} -> {} nodes | {}", self.url, self.body_bytes, self.format, self.nodes.len(), attempts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_diagnostics_for_html_body() {
+        let body = "<!DOCTYPE html>\n<html><head><title>Sign in</title></head><body><form>Please log in</form></body></html>";
+        let parsed = parse_subscription_safe(
+            "https://sub.example.com/link".to_string(), body.to_string(), Arc::new(RegexPatterns::new()), Duration::from_secs(5), Arc::new(Vec::new()), None,
+        ).await;
+        
+        assert!(parsed.nodes.is_empty());
+        assert_eq!(parsed.format, "generic");
+        assert_eq!(parsed.attempts.last().unwrap().parser, "generic");
+        assert!(parsed.attempts.last().unwrap().reason.contains("HTML page"));
+        let line = parsed.diagnostic_line();
+        assert!(line.starts_with(&format!("https://sub.example.com/link | {} bytes | generic -> 0 nodes | ", body.len())), "{}", line);
+    }
+
+    #[tokio::test]
+    async fn test_slow_parse_times_out() {
+        // half a million links take far longer than a millisecond to parse
+        let body: String = (0..500_000).map(|i| format!("vless://id@n{}.example.com:443#n{}\n", i, i)).collect();
+        let start = Instant::now();
+        let parsed = parse_subscription_safe(
+            "https://sub.example.com/huge".to_string(), body, Arc::new(RegexPatterns::new()), Duration::from_millis(1), Arc::new(Vec::new()), Some(0),
+        ).await;
+        
+        assert_eq!(parsed.format, "timeout");
+        assert!(parsed.nodes.is_empty());
+        assert_eq!(parsed.attempts.last().unwrap().parser, "timeout");
+        assert!(start.elapsed() < Duration::from_millis(500), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_clash_diagnostics_without_proxies() {
+        let (nodes, _, attempts) = detect_format_and_parse_traced("proxy-groups:\n  - name: auto\n", &RegexPatterns::new(), &[], None);
+        assert!(nodes.is_empty());
+        assert!(attempts.contains(&ParseAttempt { parser: "clash-yaml", reason: "no proxies key".to_string() }), "{:?}", attempts);
+    }
+
+    #[test]
+    fn test_protocol_filter_keeps_only_trojan() {
+        let patterns = RegexPatterns::new();
+        let body = "vless://id@vless.example.com:443\ntrojan://pw@trojan.example.com:443\nss://x@ss.example.com:8388";
+        
+        let (nodes, format) = detect_format_and_parse(body, &patterns, &["trojan".to_string()], None);
+        assert_eq!(format, "trojan");
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].host, "trojan.example.com");
+        assert_eq!(nodes[0].protocol.as_deref(), Some("trojan"));
+    }
+
+    #[test]
+    fn test_protocol_filter_applies_to_tagged_configs() {
+        let patterns = RegexPatterns::new();
+        let body = "proxies:\n  - {name: a, type: ss, server: ss.example.com, port: 8388}\n  - {name: b, type: trojan, server: tj.example.com, port: 443}\n";
+        
+        let (nodes, _) = detect_format_and_parse(body, &patterns, &["trojan".to_string()], None);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].host, "tj.example.com");
+    }
+
+    #[test]
+    fn test_format_label_clash() {
+        let patterns = RegexPatterns::new();
+        let body = "proxies:\n  - {name: a, type: ss, server: ss.example.com, port: 8388}\n";
+        assert_eq!(detect_format_and_parse(body, &patterns, &[], None).1, "clash-yaml");
+    }
+
+    #[test]
+    fn test_format_label_surge() {
+        let patterns = RegexPatterns::new();
+        let body = "[Proxy]\nUS = socks5, us.example.com, 1080\n";
+        assert_eq!(detect_format_and_parse(body, &patterns, &[], None).1, "surge");
+    }
+
+    #[test]
+    fn test_format_label_vmess() {
+        use base64::engine::general_purpose::STANDARD;
+        use base64::Engine;
+        
+        let patterns = RegexPatterns::new();
+        let body = format!("vmess://{}", STANDARD.encode(r#"{"add":"vm.example.com","port":443}"#));
+        let (nodes, format) = detect_format_and_parse(&body, &patterns, &[], None);
+        assert_eq!(format, "vmess");
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_max_nodes_per_sub_truncates() {
+        let patterns = RegexPatterns::new();
+        let body: String = (1..=5).map(|i| format!("  - {{name: n{i}, type: ss, server: s{i}.example.com, port: 443}}\n", i = i)).collect();
+        let body = format!("proxies:\n{}", body);
+        
+        assert_eq!(detect_format_and_parse(&body, &patterns, &[], Some(3)).0.len(), 3);
+        assert_eq!(detect_format_and_parse(&body, &patterns, &[], Some(0)).0.len(), 5);
+        assert_eq!(detect_format_and_parse(&body, &patterns, &[], None).0.len(), 5);
+        
+        let generic = "a.example.com:1001 b.example.com:1002 c.example.com:1003";
+        assert_eq!(detect_format_and_parse(generic, &patterns, &[], Some(2)).0.len(), 2);
+    }
+
+    #[test]
+    fn test_base64_wrapped_clash_yaml() {
+        use base64::engine::general_purpose::STANDARD;
+        use base64::Engine;
+        
+        let patterns = RegexPatterns::new();
+        let yaml = "port: 7890\nproxies:\n  - {name: a, type: trojan, server: tj.example.com, port: 443, password: x}\n  - {name: b, type: vmess, server: vm.example.com, port: 8443, uuid: y}\n";
+        // wrapped at 76 columns like most encoders do
+        let encoded = STANDARD.encode(yaml);
+        let wrapped: Vec<String> = encoded.as_bytes().chunks(76).map(|c| String::from_utf8(c.to_vec()).unwrap()).collect();
+        
+        let (nodes, format) = detect_format_and_parse(&wrapped.join("\n"), &patterns, &[], None);
+        assert_eq!(format, "clash-yaml");
+        assert_eq!(nodes, vec![
+            Node::new("tj.example.com".to_string(), 443),
+            Node::new("vm.example.com".to_string(), 8443),
+        ]);
+    }
+
+    #[test]
+    fn test_port_bounds_json_and_url() {
+        let patterns = RegexPatterns::new();
+        let clash = "proxies:\n  - {name: a, type: ss, server: zero.example.com, port: 0}\n  - {name: b, type: ss, server: max.example.com, port: 65535}\n  - {name: c, type: ss, server: over.example.com, port: 65536}\n";
+        assert_eq!(detect_format_and_parse(clash, &patterns, &[], None).0, vec![Node::new("max.example.com".to_string(), 65535)]);
+        
+        let sip008 = r#"{"version":1,"servers":[{"server":"zero.example.com","server_port":0},{"server":"max.example.com","server_port":65535},{"server":"over.example.com","server_port":65536}]}"#;
+        assert_eq!(detect_format_and_parse(sip008, &patterns, &[], None).0, vec![Node::new("max.example.com".to_string(), 65535)]);
+        
+        let links = "vless://id@zero.example.com:0\nvless://id@max.example.com:65535\nvless://id@over.example.com:65536";
+        assert_eq!(detect_format_and_parse(links, &patterns, &[], None).0, vec![Node::new("max.example.com".to_string(), 65535)]);
+    }
 }
@@ -0,0 +1,272 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::task::{JoinError, JoinHandle};
+use crate::models::{Node, NodeResult};
+use crate::shutdown::is_shutting_down;
+use crate::progress::ProgressMode;
+use tracing::warn;
+
+/// Phase 4: runs `check` on every node with at most `workers` checks in flight.
+/// With `skip` set the checks never run and every node comes back untested.
+/// Once `shutdown` is raised no further checks are started, queued ones included,
+/// and checks still running at `deadline` are aborted.
+pub async fn run_node_phase<F, Fut>(nodes: Vec<Node>, skip: bool, workers: usize, shutdown: &Arc<AtomicBool>, deadline: Option<Instant>, progress: ProgressMode, check: F) -> Vec<NodeResult>
+where
+    F: Fn(Node) -> Fut,
+    Fut: Future<Output = NodeResult> + Send + 'static,
+{
+    if skip {
+        return inventory(nodes);
+    }
+    
+    let semaphore = Arc::new(Semaphore::new(workers));
+    let counter = Arc::new(AtomicUsize::new(0));
+    let total = nodes.len();
+    let bar = progress.bar(total, "Nodes");
+    
+    let mut tasks = Vec::new();
+    for node in nodes {
+        if is_shutting_down(shutdown) {
+            break;
+        }
+        let semaphore = semaphore.clone();
+        let counter = counter.clone();
+        let bar = bar.clone();
+        let check = check(node);
+        let shutdown = shutdown.clone();
+        
+        tasks.push(tokio::spawn(async move {
+            let _permit = match semaphore.try_acquire() {
+                Ok(permit) => permit,
+                // queued behind other checks, don't start if we were told to stop meanwhile
+                Err(_) => {
+                    let permit = semaphore.acquire().await.unwrap();
+                    if is_shutting_down(&shutdown) {
+                        return None;
+                    }
+                    permit
+                }
+            };
+            let result = check.await;
+            let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            bar.inc();
+            
+            let status = result.status_label();
+            let latency = result.latency.map_or("—".to_string(), |l| format!("{:.1} ms", l));
+            bar.line(!result.is_working(), format!("Node [{}/{}] {}:{} -> {}, {}", 
+                             count, total, result.node.host, result.node.port, status, latency));
+            
+            Some(result)
+        }));
+    }
+    
+    let mut results = Vec::new();
+    for joined in join_until(tasks, deadline).await {
+        match joined {
+            Ok(Some(result)) => results.push(result),
+            Ok(None) => {}
+            Err(e) => warn!("⚠️  A node check died before reporting back: {}", e),
+        }
+    }
+    bar.finish();
+    results
+}
+
+/// Awaits `tasks` in order. Once `deadline` passes, whatever is still running gets aborted
+/// so one hung request can't hold the run past `--timeout-total`; aborted tasks are left out.
+pub async fn join_until<T>(tasks: Vec<JoinHandle<T>>, deadline: Option<Instant>) -> Vec<Result<T, JoinError>> {
+    let mut joined = Vec::with_capacity(tasks.len());
+    for mut task in tasks {
+        let result = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline.into(), &mut task).await {
+                Ok(result) => result,
+                Err(_) => {
+                    task.abort();
+                    task.await
+                }
+            },
+            None => task.await,
+        };
+        match result {
+            Err(e) if e.is_cancelled() => {}
+            result => joined.push(result),
+        }
+    }
+    joined
+}
+
+/// `--warmup`: one throwaway `connect` per unique host before the measured checks, so
+/// none of them pays for a cold DNS lookup. Runs `workers` at a time, outcomes are ignored.
+/// Returns how many hosts got a connect.
+pub async fn warm_up_hosts<F, Fut>(nodes: &[Node], workers: usize, shutdown: &Arc<AtomicBool>, connect: F) -> usize
+where
+    F: Fn(Node) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut seen = HashSet::new();
+    let semaphore = Arc::new(Semaphore::new(workers));
+    let mut tasks = Vec::new();
+    for node in nodes.iter().filter(|node| seen.insert(node.host.as_str())) {
+        if is_shutting_down(shutdown) {
+            break;
+        }
+        let semaphore = semaphore.clone();
+        let connect = connect(node.clone());
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire().await.unwrap();
+            connect.await;
+        }));
+    }
+    
+    let warmed = tasks.len();
+    for task in tasks {
+        let _ = task.await;
+    }
+    warmed
+}
+
+/// Untested results (no status, no latency) for writing a plain node inventory
+pub fn inventory(nodes: Vec<Node>) -> Vec<NodeResult> {
+    nodes.into_iter().map(|node| NodeResult::new(node, None, None)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes() -> Vec<Node> {
+        vec![
+            Node::new("a.example.com".to_string(), 443),
+            Node::new("b.example.com".to_string(), 443),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_node_phase_skipped() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let results = run_node_phase(nodes(), true, 4, &Arc::new(AtomicBool::new(false)), None, ProgressMode::default(), |node| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move { NodeResult::new(node, Some(0), Some(1.0)) }
+        }).await;
+        
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.status.is_none() && r.latency.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_node_phase_runs_checks() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let results = run_node_phase(nodes(), false, 4, &Arc::new(AtomicBool::new(false)), None, ProgressMode::default(), |node| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move { NodeResult::new(node, Some(0), Some(1.0)) }
+        }).await;
+        
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(results.iter().all(|r| r.is_working()));
+    }
+
+    #[tokio::test]
+    async fn test_node_phase_stops_spawning_on_shutdown() {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let calls = AtomicUsize::new(0);
+        let results = run_node_phase(nodes(), false, 4, &shutdown, None, ProgressMode::default(), |node| {
+            // first check "receives" the Ctrl-C
+            calls.fetch_add(1, Ordering::SeqCst);
+            shutdown.store(true, Ordering::SeqCst);
+            async move { NodeResult::new(node, Some(0), Some(1.0)) }
+        }).await;
+        
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_node_phase_drops_queued_checks_on_shutdown() {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        // one worker, so the second check is still waiting for a permit when the first raises the flag
+        let results = run_node_phase(nodes(), false, 1, &shutdown, None, ProgressMode::default(), |node| {
+            let shutdown = shutdown.clone();
+            async move {
+                shutdown.store(true, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                NodeResult::new(node, Some(0), Some(1.0))
+            }
+        }).await;
+        
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_node_phase_skips_queued_checks_when_stopped_mid_run() {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let started = Arc::new(AtomicUsize::new(0));
+        let nodes: Vec<Node> = (1..=5).map(|i| Node::new(format!("n{}.example.com", i), 443)).collect();
+        
+        // Ctrl-C lands while the first check is in flight and the other four wait for the single worker
+        let raiser = shutdown.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            raiser.store(true, Ordering::SeqCst);
+        });
+        let results = run_node_phase(nodes, false, 1, &shutdown, None, ProgressMode::default(), |node| {
+            let started = started.clone();
+            async move {
+                started.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                NodeResult::new(node, Some(0), Some(1.0))
+            }
+        }).await;
+        
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_node_phase_keeps_going_past_a_panicked_check() {
+        let results = run_node_phase(nodes(), false, 4, &Arc::new(AtomicBool::new(false)), None, ProgressMode::default(), |node| async move {
+            if node.host.starts_with('a') {
+                panic!("check blew up");
+            }
+            NodeResult::new(node, Some(0), Some(1.0))
+        }).await;
+        
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].node.host, "b.example.com");
+    }
+
+    #[tokio::test]
+    async fn test_join_until_aborts_hung_tasks_at_deadline() {
+        let tasks = vec![
+            tokio::spawn(async { 1 }),
+            tokio::spawn(std::future::pending::<i32>()),
+            tokio::spawn(async { 3 }),
+        ];
+        let deadline = Instant::now() + std::time::Duration::from_millis(50);
+        
+        let joined: Vec<i32> = join_until(tasks, Some(deadline)).await.into_iter().map(Result::unwrap).collect();
+        assert_eq!(joined, vec![1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_connects_once_per_host() {
+        let mut nodes = nodes();
+        nodes.push(Node::new("a.example.com".to_string(), 8443));
+        nodes.push(Node::new("b.example.com".to_string(), 443));
+        let connects = Arc::new(AtomicUsize::new(0));
+        
+        let warmed = warm_up_hosts(&nodes, 4, &Arc::new(AtomicBool::new(false)), |_| {
+            let connects = connects.clone();
+            async move {
+                connects.fetch_add(1, Ordering::SeqCst);
+            }
+        }).await;
+        
+        assert_eq!(warmed, 2);
+        assert_eq!(connects.load(Ordering::SeqCst), 2);
+    }
+}
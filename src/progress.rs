@@ -0,0 +1,88 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use tracing::{debug, warn};
+
+/// Whether the phases draw a progress bar (per-item lines are logged either way)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProgressMode {
+    pub enabled: bool,
+}
+
+impl ProgressMode {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+    
+    pub fn bar(self, len: usize, label: &str) -> PhaseProgress {
+        let bar = self.enabled.then(|| {
+            let bar = ProgressBar::new(len as u64);
+            bar.set_style(
+                ProgressStyle::with_template("{prefix:>6} [{bar:40}] {pos}/{len} ETA {eta}")
+                    .unwrap()
+                    .progress_chars("=> "),
+            );
+            bar.set_prefix(label.to_string());
+            bar
+        });
+        PhaseProgress { bar }
+    }
+}
+
+/// One phase's progress. Cheap to clone into spawned tasks.
+#[derive(Clone)]
+pub struct PhaseProgress {
+    bar: Option<ProgressBar>,
+}
+
+impl PhaseProgress {
+    /// Logs a per-item line, at warn level when the item `failed` and debug otherwise.
+    /// The bar gets hidden while the line is written so the two don't trample each other.
+    pub fn line(&self, failed: bool, msg: String) {
+        let log = || if failed {
+            warn!("{}", msg)
+        } else {
+            debug!("{}", msg)
+        };
+        match &self.bar {
+            Some(bar) => bar.suspend(log),
+            None => log(),
+        }
+    }
+    
+    pub fn inc(&self) {
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+    }
+    
+    pub fn position(&self) -> Option<u64> {
+        self.bar.as_ref().map(|bar| bar.position())
+    }
+    
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_advances_without_tty() {
+        let progress = ProgressMode::new(true).bar(3, "Nodes");
+        progress.inc();
+        progress.line(false, "logged above the bar".to_string());
+        progress.clone().inc();
+        assert_eq!(progress.position(), Some(2));
+        progress.finish();
+    }
+
+    #[test]
+    fn test_disabled_has_no_bar() {
+        let progress = ProgressMode::default().bar(3, "Nodes");
+        progress.inc();
+        assert_eq!(progress.position(), None);
+    }
+}
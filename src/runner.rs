@@ -0,0 +1,726 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use clap::Parser;
+use rand::seq::SliceRandom;
+use reqwest::Client;
+use tokio::sync::Semaphore;
+
+use crate::config::{AddressFamily, Args, CheckMode, ReportFormat, DNS_CACHE_TTL, MAX_DNS_CACHE_ENTRIES, STOP_POLL_INTERVAL, DUP_STATS_TOP, MAX_SPEED_TEST_BYTES};
+use crate::error::ProxyYoinkerError;
+use crate::models::{DupStats, Node, NodeResult, RegexPatterns, RunSummary, SourceStats, StatusHistogram, UrlResult};
+use crate::parsers::parse_subscription_safe;
+use crate::parsers::proxy_urls::expand_cdn_hosts;
+use crate::network::checker::connect_node;
+use crate::network::{build_client, http_check, node_http_check, node_proxy_check, tcp_connect_check, fetch_body, dedup_by_resolved_ip, DnsCache, DnsLimiter, HostLimiter, RequestRateLimiter, AdaptiveLimiter, ProxyChain, PingClients, ping_check, check_with_confirm, node_speed_test, tls_check, tls_connector, Retries};
+use crate::io::{ReportColumns, dedup_node_results, write_node_report, write_node_report_json, write_node_report_csv, write_url_report, append_url_report, append_node_report, write_clash_yaml, write_raw_uris, write_summary, write_diagnostics, filter_by_max_latency, within_max_latency, filter_working, spawn_node_report_stream, spawn_ndjson_stream, gather_text, load_nodes_file, fetch_cached, BodyCache};
+use crate::eta::{EtaEstimator, Phase};
+use crate::utils::format_duration;
+use crate::discovery::{extract_urls, follow_nested_urls};
+use crate::filters::{ExcludeList, filter_excluded, filter_by_family, filter_local, filter_small_bodies, limit_nodes, node_rng};
+use crate::geoip::{GeoIp, enrich_with_geoip};
+use crate::pipeline::{join_until, run_node_phase, warm_up_hosts};
+use crate::shutdown::is_shutting_down;
+use crate::progress::ProgressMode;
+use tracing::{info, warn};
+
+/// Everything `run` needs, same fields and defaults as the command line
+pub type RunConfig = Args;
+
+impl Args {
+    /// Command line defaults for `input`, tweak the fields from there. A bad `PY_*`
+    /// variable comes back as an error rather than exiting the process.
+    pub fn for_input(input: &str) -> Result<Self, ProxyYoinkerError> {
+        Args::try_parse_from(["proxy-yoink-er", input]).map_err(|e| ProxyYoinkerError::Config(e.to_string()))
+    }
+}
+
+/// What a run found. The report files are written along the way as well.
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    /// Subscription URLs that answered 200
+    pub working_urls: Vec<UrlResult>,
+    /// Every node that was checked (or just listed, with `no_node_test`)
+    pub node_results: Vec<NodeResult>,
+    pub summary: RunSummary,
+    /// Ctrl-C cut the run short
+    pub interrupted: bool,
+    /// `timeout_total` ran out before all phases finished
+    pub timed_out: bool,
+}
+
+/// Runs all four phases: URL check, fetch, parse, node test
+pub async fn run(config: RunConfig) -> Result<RunReport, ProxyYoinkerError> {
+    run_with_shutdown(config, &AtomicBool::new(false)).await
+}
+
+/// `run`, but stops starting new work once `shutdown` is raised (see `shutdown::install_ctrl_c_handler`)
+/// or `timeout_total` runs out. After Ctrl-C in-flight requests finish, at the deadline they're
+/// aborted. Partial results are still written either way.
+pub async fn run_with_shutdown(args: RunConfig, shutdown: &AtomicBool) -> Result<RunReport, ProxyYoinkerError> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let deadline = args.timeout_total.map(|secs| Instant::now() + Duration::from_secs(secs));
+    let phases = run_phases(args, stop.clone(), deadline);
+    tokio::pin!(phases);
+    
+    let timed_out = tokio::select! {
+        // aborted tasks can end the phases before the watcher gets to see the deadline
+        report = &mut phases => return report.map(|report| RunReport {
+            timed_out: deadline.is_some_and(|deadline| Instant::now() >= deadline),
+            ..report
+        }),
+        timed_out = watch_for_stop(shutdown, &stop, deadline) => timed_out,
+    };
+    
+    let mut report = phases.await?;
+    report.interrupted = is_shutting_down(shutdown);
+    report.timed_out = timed_out;
+    Ok(report)
+}
+
+/// Raises `stop` on Ctrl-C or once `deadline` passes, returns whether it was the deadline
+async fn watch_for_stop(shutdown: &AtomicBool, stop: &AtomicBool, deadline: Option<Instant>) -> bool {
+    loop {
+        if is_shutting_down(shutdown) {
+            stop.store(true, Ordering::SeqCst);
+            return false;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            stop.store(true, Ordering::SeqCst);
+            warn!("⏰ --timeout-total reached, abandoning in-flight work and writing partial results");
+            return true;
+        }
+        tokio::time::sleep(STOP_POLL_INTERVAL).await;
+    }
+}
+
+async fn run_phases(args: RunConfig, stop: Arc<AtomicBool>, deadline: Option<Instant>) -> Result<RunReport, ProxyYoinkerError> {
+    let start_time = Instant::now();
+    let args = args.resolve_output_paths();
+    if let Some(dir) = &args.output_dir {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let node_timeout = Duration::from_secs(args.node_timeout);
+    let retries = match args.retry_budget {
+        Some(budget) => Retries::new(args.retries).with_budget(budget),
+        None => Retries::new(args.retries),
+    };
+    let method = args.method;
+    let progress = ProgressMode::new(args.progress);
+    
+    info!("🚀 Starting subscription analysis...");
+    
+    let exclude = match &args.exclude {
+        Some(path) => Some(ExcludeList::load(path).await?),
+        None => None,
+    };
+    
+    let patterns = match &args.url_pattern {
+        Some(pattern) => RegexPatterns::new().with_url_pattern(pattern).map_err(|e| ProxyYoinkerError::Parse(format!("invalid --url-pattern: {}", e)))?,
+        None => RegexPatterns::new(),
+    };
+    let patterns = Arc::new(patterns);
+    let protocols: Arc<Vec<String>> = Arc::new(args.protocols.iter().map(|p| p.trim().to_lowercase()).collect());
+    let proxy_chain = args.upstream_proxy.as_deref().map(ProxyChain::new).transpose()?.map(Arc::new);
+    if args.probe_quorum == 0 || args.probe_quorum > args.probe_urls.len() {
+        return Err(ProxyYoinkerError::Config(format!("--probe-quorum must be between 1 and the number of --probe-url endpoints ({})", args.probe_urls.len())));
+    }
+    if !(args.confirm_fraction > 0.0 && args.confirm_fraction <= 1.0) {
+        return Err(ProxyYoinkerError::Config(format!("--confirm-fraction must be in (0, 1], got {}", args.confirm_fraction)));
+    }
+    if args.max_dns_workers == 0 {
+        return Err(ProxyYoinkerError::Config("--max-dns-workers must be at least 1".to_string()));
+    }
+    let dns = DnsLimiter::new(args.max_dns_workers);
+    // reqwest resolves through the limiter too, so fetches and HTTP checks share the cap
+    let client = build_client(&args, &dns)?;
+    let rate_limiter = args.rate_limit.map(|rps| Arc::new(RequestRateLimiter::new(rps)));
+    
+    let (working_urls, total_urls, total_eta, mut eta, mut all_nodes) = match &args.nodes_file {
+        // --nodes-file goes straight to phase 4, nothing to check, fetch or parse
+        Some(path) => {
+            let nodes = load_nodes_file(path, &patterns, &protocols).await?;
+            info!("📂 Loaded {} nodes from {}, skipping the subscription phases", nodes.len(), path);
+            let eta = EtaEstimator::new(0, args.max_io_workers, args.max_parse_workers);
+            (Vec::new(), 0, eta.total(), eta, nodes)
+        }
+        None => collect_subscription_nodes(&args, &client, &retries, &rate_limiter, &patterns, &protocols, &stop, deadline).await?,
+    };
+    
+    if args.resolve_dedup {
+        let before = all_nodes.len();
+        all_nodes = dedup_by_resolved_ip(all_nodes, |host| dns.clone().resolve_host(host)).await;
+        info!("🧬 Resolved hosts: {} nodes collapsed to {} unique IP:port pairs", before, all_nodes.len());
+    }
+    
+    if !args.keep_local {
+        let before = all_nodes.len();
+        all_nodes = filter_local(all_nodes);
+        if before > all_nodes.len() {
+            info!("🚫 Skipped {} loopback or placeholder nodes (--keep-local tests them)", before - all_nodes.len());
+        }
+    }
+    
+    if let Some(exclude) = &exclude {
+        let before = all_nodes.len();
+        all_nodes = filter_excluded(all_nodes, exclude, |host| dns.clone().resolve_host(host)).await;
+        info!("🚫 Excluded {} nodes matching the exclude list", before - all_nodes.len());
+    }
+    
+    if args.family != AddressFamily::Both {
+        let before = all_nodes.len();
+        all_nodes = filter_by_family(all_nodes, args.family, |host| dns.clone().resolve_host_all(host)).await;
+        info!("🚫 Skipped {} nodes without a {:?} address", before - all_nodes.len(), args.family);
+    }
+    
+    let mut rng = node_rng(args.seed);
+    if let Some(limit) = args.limit {
+        let before = all_nodes.len();
+        all_nodes = limit_nodes(all_nodes, limit, args.sample, &mut rng);
+        if before > all_nodes.len() {
+            info!("✂️  --limit {}: skipping {} of {} nodes", limit, before - all_nodes.len(), before);
+        }
+    }
+    if args.shuffle {
+        all_nodes.shuffle(&mut rng);
+    }
+    
+    eta.set_items(Phase::Nodes, if args.no_node_test { 0 } else { all_nodes.len() });
+    log_eta(&eta);
+    
+    // Phase 4: Test nodes
+    let mut check_mode = args.check_mode;
+    let mut ping_clients = None;
+    if check_mode == CheckMode::Ping && !args.verify_proxy && !args.no_node_test {
+        match PingClients::new() {
+            Ok(clients) => ping_clients = Some(Arc::new(clients)),
+            Err(e) => {
+                warn!("⚠️  {}, falling back to --check-mode tcp", e);
+                check_mode = CheckMode::Tcp;
+            }
+        }
+    }
+    if args.no_node_test {
+        info!("⏭️  Skipping node testing, writing inventory of {} nodes", all_nodes.len());
+        if args.max_latency.is_some() {
+            warn!("⚠️  --max-latency has nothing to filter in an untested inventory, ignoring it");
+        }
+    } else {
+        let mode = if args.verify_proxy { "proxy".to_string() } else { check_mode.to_string() };
+        info!("🌐 Testing {} node URLs with {} workers ({} mode)...", all_nodes.len(), args.max_io_workers, mode);
+    }
+    let verify_proxy = args.verify_proxy;
+    let only_working = args.only_working;
+    let max_latency = args.max_latency;
+    let probe_quorum = args.probe_quorum;
+    let confirm_fraction = args.confirm.then_some(args.confirm_fraction);
+    let speed_test = args.speed_test.map(|bytes| {
+        if bytes > MAX_SPEED_TEST_BYTES {
+            warn!("⚠️  --speed-test {} is over the {} byte cap, downloading {} instead", bytes, MAX_SPEED_TEST_BYTES, MAX_SPEED_TEST_BYTES);
+        }
+        let bytes = bytes.min(MAX_SPEED_TEST_BYTES);
+        (args.speed_test_url.replace("{bytes}", &bytes.to_string()), bytes)
+    });
+    let speed_test_timeout = Duration::from_secs(args.speed_test_timeout);
+    let tls_connector = tls_connector();
+    let dns_cache = args.dns_cache.then(|| Arc::new(DnsCache::new(DNS_CACHE_TTL, MAX_DNS_CACHE_ENTRIES)));
+    
+    let geoip = match &args.geoip {
+        Some(path) if !args.no_node_test => match GeoIp::open(path) {
+            Ok(geoip) => Some(Arc::new(geoip)),
+            Err(e) => {
+                warn!("Couldn't load GeoIP database {} ({}), skipping enrichment", path, e);
+                None
+            }
+        },
+        _ => None,
+    };
+    // driven by what this run measures, so the streamed and batch reports get the same columns
+    let columns = ReportColumns {
+        ttfb: args.show_ttfb,
+        tier: args.show_tier,
+        protocol: args.no_node_test,
+        country: geoip.is_some(),
+        speed: speed_test.is_some() && verify_proxy,
+    };
+    let host_limiter = args.per_host_limit.filter(|&n| n > 0).map(|n| Arc::new(HostLimiter::new(n)));
+    
+    let streaming = args.stream && args.format == ReportFormat::Md && !args.no_node_test && !args.append && args.row_template.is_none();
+    if args.stream && !streaming {
+        warn!("--stream only applies to a fresh Markdown report of tested nodes without --row-template, writing at the end instead");
+    }
+    if args.append && args.format != ReportFormat::Md {
+        warn!("--append only merges Markdown reports, the {:?} node report will be overwritten", args.format);
+    }
+    let (stream_tx, stream_handle) = if streaming {
+        let (tx, handle) = spawn_node_report_stream(&args.node_out, columns, args.tiers).await?;
+        (Some(tx), Some(handle))
+    } else {
+        (None, None)
+    };
+    let (ndjson_tx, ndjson_handle) = match &args.ndjson {
+        Some(path) => {
+            let (tx, handle) = spawn_ndjson_stream(path).await?;
+            (Some(tx), Some(handle))
+        }
+        None => (None, None),
+    };
+    
+    // only the connect-based checks read the DNS cache the warmup fills, reqwest resolves on its own
+    let warmup_helps = dns_cache.is_some() && !verify_proxy && check_mode != CheckMode::Http;
+    if args.warmup && !args.no_node_test && !warmup_helps {
+        info!("🔥 Skipping --warmup, it only pre-fills the DNS cache used by --check-mode tcp, tls and ping");
+    } else if args.warmup && !args.no_node_test && !is_shutting_down(&stop) {
+        let warmed = warm_up_hosts(&all_nodes, args.max_io_workers, &stop, |node| {
+            let dns_cache = dns_cache.clone();
+            let dns = dns.clone();
+            let host_limiter = host_limiter.clone();
+            let rate_limiter = rate_limiter.clone();
+            async move {
+                // the same --per-host-limit and --rate-limit as the checks that follow
+                let _host_permit = match &host_limiter {
+                    Some(limiter) => Some(limiter.acquire(&node.host).await),
+                    None => None,
+                };
+                if let Some(limiter) = &rate_limiter {
+                    limiter.wait().await;
+                }
+                let _ = tokio::time::timeout(node_timeout, connect_node(&node, dns_cache.as_deref(), &dns)).await;
+            }
+        }).await;
+        info!("🔥 Warmed up {} hosts before measuring", warmed);
+    }
+    
+    let node_results = run_node_phase(all_nodes, args.no_node_test, args.max_io_workers, &stop, deadline, progress, |node| {
+        let client = client.clone();
+        let probe_urls = args.probe_urls.clone();
+        let dns_cache = dns_cache.clone();
+        let stream_tx = stream_tx.clone();
+        let ndjson_tx = ndjson_tx.clone();
+        let host_limiter = host_limiter.clone();
+        let rate_limiter = rate_limiter.clone();
+        let proxy_chain = proxy_chain.clone();
+        let ping_clients = ping_clients.clone();
+        let speed_test = speed_test.clone();
+        let geoip = geoip.clone();
+        let tls_connector = tls_connector.clone();
+        let dns = dns.clone();
+        let retries = retries.clone();
+        
+        async move {
+            let _host_permit = match &host_limiter {
+                Some(limiter) => Some(limiter.acquire(&node.host).await),
+                None => None,
+            };
+            if let Some(limiter) = &rate_limiter {
+                limiter.wait().await;
+            }
+            let (client, probe_urls, dns_cache, proxy_chain, ping_clients, tls_connector, dns, retries) = (&client, &probe_urls, &dns_cache, &proxy_chain, &ping_clients, &tls_connector, &dns, &retries);
+            // shared state goes in by reference so --confirm can run the same check twice
+            let check = move |node: Node| async move {
+                if verify_proxy {
+                    let chained_latency = match proxy_chain {
+                        Some(chain) => chain.check(&node, &probe_urls[0], node_timeout, retries).await,
+                        None => None,
+                    };
+                    NodeResult {
+                        chained_latency,
+                        ..node_proxy_check(node, probe_urls, probe_quorum, node_timeout, retries, dns).await
+                    }
+                } else {
+                    match check_mode {
+                        CheckMode::Http => node_http_check(client, node, method, node_timeout, retries).await,
+                        CheckMode::Tcp => tcp_connect_check(node, node_timeout, retries, dns_cache.as_deref(), dns).await,
+                        CheckMode::Ping => match ping_clients {
+                            Some(clients) => ping_check(clients, node, node_timeout, retries, dns_cache.as_deref(), dns).await,
+                            None => tcp_connect_check(node, node_timeout, retries, dns_cache.as_deref(), dns).await,
+                        },
+                        CheckMode::Tls => tls_check(tls_connector, node, node_timeout, retries, dns_cache.as_deref(), dns).await,
+                    }
+                }
+            };
+            let mut result = check_with_confirm(node, node_timeout, confirm_fraction, check).await;
+            if let Some((url, bytes)) = &speed_test {
+                if verify_proxy && result.is_working() {
+                    result.throughput = node_speed_test(&result.node, url, *bytes, speed_test_timeout, dns).await;
+                }
+            }
+            if let Some(geoip) = &geoip {
+                enrich_with_geoip(std::slice::from_mut(&mut result), geoip, |host| dns.clone().resolve_host(host)).await;
+            }
+            if let Some(tx) = &stream_tx {
+                // the same filters the batch writer gets, a streamed row can't be taken back
+                let kept = match max_latency {
+                    Some(max_latency) => within_max_latency(&result, max_latency),
+                    None => result.is_working() || !only_working,
+                };
+                if kept {
+                    let _ = tx.send(result.clone());
+                }
+            }
+            if let Some(tx) = &ndjson_tx {
+                let _ = tx.send(result.clone());
+            }
+            result
+        }
+    }).await;
+    
+    if let Some(tx) = &ndjson_tx {
+        // nothing got checked, so the untested inventory is all there is to stream
+        if args.no_node_test {
+            for result in &node_results {
+                let _ = tx.send(result.clone());
+            }
+        }
+    }
+    
+    drop(stream_tx);
+    drop(ndjson_tx);
+    for handle in [stream_handle, ndjson_handle].into_iter().flatten() {
+        handle.await??;
+    }
+    
+    // Write node report, one row per host:port whatever the format
+    let report_results = dedup_node_results(&node_results);
+    let mut report_results = match args.max_latency {
+        Some(max_latency) if !args.no_node_test => filter_by_max_latency(&report_results, max_latency),
+        _ => report_results,
+    };
+    if args.only_working {
+        report_results = filter_working(&report_results);
+    }
+    
+    match args.format {
+        ReportFormat::Md if streaming => info!("📝 Node report was streamed to {}", args.node_out),
+        ReportFormat::Md if args.append => append_node_report(&args.node_out, &report_results, columns, args.sort, &args.tiers).await?,
+        ReportFormat::Md => write_node_report(&args.node_out, &report_results, columns, args.sort, &args.tiers, args.row_template.as_ref()).await?,
+        ReportFormat::Json => write_node_report_json(&args.node_out, &report_results).await?,
+        ReportFormat::Csv => write_node_report_csv(&args.node_out, &report_results).await?,
+    }
+    
+    if let Some(clash_out) = &args.clash_out {
+        write_clash_yaml(clash_out, &report_results).await?;
+    }
+    
+    if let Some(raw_out) = &args.raw_out {
+        write_raw_uris(raw_out, &report_results).await?;
+    }
+    
+    // Final timing
+    let total_elapsed = start_time.elapsed().as_secs_f64();
+    let summary = RunSummary::new(total_urls, working_urls.len(), &node_results, total_elapsed);
+    if let Some(summary_out) = &args.summary_out {
+        write_summary(summary_out, &summary).await?;
+    }
+    if args.source_stats && !args.no_node_test {
+        info!("📡 Per-source results:");
+        for stats in SourceStats::collect(&node_results) {
+            let median = stats.median_latency.map_or("—".to_string(), |ms| format!("{:.0}ms", ms));
+            info!("   {} - {}/{} reachable, median {}", stats.source, stats.reachable, stats.total, median);
+        }
+    }
+    let interrupted = is_shutting_down(&stop);
+    if interrupted {
+        warn!("🛑 Stopped early - partial results were written");
+    }
+    info!("💾 Reports: {}", args.report_paths().join(", "));
+    info!("🏁 Done! Total time: {} (estimated: {})", 
+             format_duration(total_elapsed), format_duration(total_eta));
+    
+    Ok(RunReport { working_urls, node_results, summary, interrupted, timed_out: false })
+}
+
+/// Phases 1-3: check the subscription URLs, fetch the working ones and parse their nodes.
+/// Returns the working URLs, how many were checked, the first time estimate, the ETA and the unique nodes.
+#[allow(clippy::too_many_arguments)] // the run-wide handles phase 4 shares as well
+async fn collect_subscription_nodes(
+    args: &RunConfig,
+    client: &Client,
+    retries: &Retries,
+    rate_limiter: &Option<Arc<RequestRateLimiter>>,
+    patterns: &Arc<RegexPatterns>,
+    protocols: &Arc<Vec<String>>,
+    stop: &Arc<AtomicBool>,
+    deadline: Option<Instant>,
+) -> Result<(Vec<UrlResult>, usize, f64, EtaEstimator, Vec<Node>), ProxyYoinkerError> {
+    let url_timeout = Duration::from_secs(args.url_timeout);
+    let parse_timeout = Duration::from_secs(args.parse_timeout);
+    let method = args.method;
+    let progress = ProgressMode::new(args.progress);
+    
+    // Gather text and extract URLs
+    let raw_text = gather_text(&args.input, client, url_timeout, args.glob.as_deref()).await?;
+    let urls: Vec<String> = extract_urls(&raw_text, patterns)
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    
+    let mut seen_urls: HashSet<String> = urls.iter().cloned().collect();
+    let total_urls = urls.len();
+    let mut eta = EtaEstimator::new(total_urls, args.max_io_workers, args.max_parse_workers);
+    let total_eta = eta.total();
+    let pre_node_eta = total_eta - eta.node_phase();
+    
+    info!("📊 Found {} URLs - Estimated total time: {}", total_urls, format_duration(total_eta));
+    info!("   (URL check + fetch + parse: ~{}, node testing: ~{})", 
+             format_duration(pre_node_eta), format_duration(total_eta - pre_node_eta));
+    
+    // Phase 1: URL checking
+    let phase_start = Instant::now();
+    info!("🔍 Testing {} subscription URLs with {} workers...", total_urls, args.max_io_workers);
+    let io_limiter = || Arc::new(if args.adaptive {
+        AdaptiveLimiter::adaptive(args.max_io_workers)
+    } else {
+        AdaptiveLimiter::fixed(args.max_io_workers)
+    });
+    let url_limiter = io_limiter();
+    let url_counter = Arc::new(AtomicUsize::new(0));
+    let url_bar = progress.bar(total_urls, "URLs");
+    
+    let mut url_tasks = Vec::new();
+    for url in urls {
+        if is_shutting_down(stop) {
+            break;
+        }
+        let client = client.clone();
+        let limiter = url_limiter.clone();
+        let counter = url_counter.clone();
+        let bar = url_bar.clone();
+        let rate_limiter = rate_limiter.clone();
+        let retries = retries.clone();
+        let stop = stop.clone();
+    
+        url_tasks.push(tokio::spawn(async move {
+            let _permit = limiter.acquire().await;
+            if is_shutting_down(&stop) {
+                return None;
+            }
+            if let Some(limiter) = &rate_limiter {
+                limiter.wait().await;
+            }
+            let result = http_check(&client, &url, method, url_timeout, &retries).await;
+            limiter.record(result.status.is_some());
+            let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            bar.inc();
+    
+            let status = result.status_label();
+            let latency = result.latency.map_or("—".to_string(), |l| format!("{:.1} ms", l));
+            bar.line(result.status != Some(200), format!("URL [{}/{}] {} -> {}, {}", count, total_urls, result.url, status, latency));
+    
+            Some(result)
+        }));
+    }
+    
+    let mut url_results = Vec::new();
+    for joined in join_until(url_tasks, deadline).await {
+        url_results.extend(joined?);
+    }
+    url_bar.finish();
+    if args.adaptive {
+        info!("   Adaptive concurrency ended at {} workers", url_limiter.limit());
+    }
+    
+    if !url_results.is_empty() {
+        info!("📶 URL statuses: {}", StatusHistogram::collect(&url_results).format());
+    }
+    let working_urls: Vec<_> = url_results
+        .into_iter()
+        .filter(|r| r.status == Some(200))
+        .collect();
+    
+    let working_url_count = working_urls.len();
+    info!("✅ Found {} working URLs out of {}", working_url_count, total_urls);
+    eta.phase_done(Phase::Urls, total_urls, phase_start.elapsed().as_secs_f64());
+    eta.set_items(Phase::Fetch, working_url_count);
+    eta.set_items(Phase::Parse, working_url_count);
+    log_eta(&eta);
+    
+    // Write URL report
+    if args.append {
+        append_url_report(&args.url_out, &working_urls, args.show_ttfb).await?;
+    } else {
+        write_url_report(&args.url_out, &working_urls, args.show_ttfb).await?;
+    }
+    
+    // Phase 2: Fetch bodies
+    let phase_start = Instant::now();
+    info!("📥 Fetching bodies for {} subscriptions with {} workers...", working_urls.len(), args.max_io_workers);
+    let fetch_limiter = io_limiter();
+    let fetch_counter = Arc::new(AtomicUsize::new(0));
+    
+    let body_cache = match &args.cache_dir {
+        Some(dir) => Some(Arc::new(BodyCache::open(dir, Duration::from_secs(args.cache_ttl)).await?)),
+        None => None,
+    };
+    
+    let mut fetch_tasks = Vec::new();
+    let fetch_tasks_len = working_urls.len();
+    let fetch_bar = progress.bar(fetch_tasks_len, "Fetch");
+    for url in working_urls.iter().map(|r| r.url.clone()) {
+        if is_shutting_down(stop) {
+            break;
+        }
+        let client = client.clone();
+        let limiter = fetch_limiter.clone();
+        let counter = fetch_counter.clone();
+        let bar = fetch_bar.clone();
+        let rate_limiter = rate_limiter.clone();
+        let stop = stop.clone();
+        let cache = body_cache.clone();
+    
+        fetch_tasks.push(tokio::spawn(async move {
+            let _permit = limiter.acquire().await;
+            if is_shutting_down(&stop) {
+                return (url, None);
+            }
+            if let Some(limiter) = &rate_limiter {
+                limiter.wait().await;
+            }
+            let (url, body) = fetch_cached(cache.as_deref(), url, |url| async move {
+                fetch_body(&client, &url, url_timeout).await
+            }).await;
+            limiter.record(body.is_some());
+            let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            bar.inc();
+    
+            let size = body.as_ref().map_or(0, |b| b.len());
+            let status = if body.is_some() { "OK" } else { "FAIL" };
+            bar.line(body.is_none(), format!("Fetch [{}/{}] {} -> {}, {} chars", count, fetch_tasks_len, url, status, size));
+    
+            (url, body)
+        }));
+    }
+    
+    let mut bodies = Vec::new();
+    for joined in join_until(fetch_tasks, deadline).await {
+        let (url, body) = joined?;
+        if let Some(body) = body {
+            bodies.push((url, body));
+        }
+    }
+    fetch_bar.finish();
+    if args.adaptive {
+        info!("   Adaptive concurrency ended at {} workers", fetch_limiter.limit());
+    }
+    
+    if args.follow_depth > 0 && !is_shutting_down(stop) {
+        info!("🔗 Following nested subscription URLs up to depth {}...", args.follow_depth);
+        let follow = follow_nested_urls(&bodies, &mut seen_urls, patterns, args.follow_depth, |url| {
+            let client = client.clone();
+            let limiter = fetch_limiter.clone();
+            let rate_limiter = rate_limiter.clone();
+            let stop = stop.clone();
+            let cache = body_cache.clone();
+            async move {
+                let _permit = limiter.acquire().await;
+                if is_shutting_down(&stop) {
+                    return (url, None);
+                }
+                if let Some(limiter) = &rate_limiter {
+                    limiter.wait().await;
+                }
+                let (url, body) = fetch_cached(cache.as_deref(), url, |url| async move {
+                    fetch_body(&client, &url, url_timeout).await
+                }).await;
+                limiter.record(body.is_some());
+                (url, body)
+            }
+        });
+        let nested = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), follow).await.unwrap_or_default(),
+            None => follow.await,
+        };
+        info!("   Fetched {} nested subscriptions", nested.len());
+        bodies.extend(nested);
+    }
+    
+    if args.min_body_size > 0 {
+        let before = bodies.len();
+        bodies = filter_small_bodies(bodies, args.min_body_size);
+        info!("🗑️  Skipped {} subscriptions under {} bytes", before - bodies.len(), args.min_body_size);
+    }
+    
+    eta.phase_done(Phase::Fetch, fetch_tasks_len, phase_start.elapsed().as_secs_f64());
+    eta.set_items(Phase::Parse, bodies.len());
+    log_eta(&eta);
+    
+    // Phase 3: Parse subscriptions
+    let phase_start = Instant::now();
+    info!("🔧 Parsing nodes from {} subscriptions with {} workers...", bodies.len(), args.max_parse_workers);
+    let parse_semaphore = Arc::new(Semaphore::new(args.max_parse_workers));
+    let parse_counter = Arc::new(AtomicUsize::new(0));
+    
+    let mut parse_tasks = Vec::new();
+    let parse_tasks_len = bodies.len();
+    let max_nodes_per_sub = args.max_nodes_per_sub;
+    let expand_cdn = args.expand_cdn;
+    let parse_bar = progress.bar(parse_tasks_len, "Parse");
+    for (url, body) in bodies {
+        if is_shutting_down(stop) {
+            break;
+        }
+        let semaphore = parse_semaphore.clone();
+        let counter = parse_counter.clone();
+        let patterns = patterns.clone();
+        let protocols = protocols.clone();
+        let bar = parse_bar.clone();
+        let stop = stop.clone();
+    
+        parse_tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire().await.unwrap();
+            if is_shutting_down(&stop) {
+                return (Vec::new(), None);
+            }
+            let parse_start = Instant::now();
+            let parsed = parse_subscription_safe(url, body, patterns, parse_timeout, protocols, max_nodes_per_sub).await;
+            let diagnostic = parsed.diagnostic_line();
+            let (url, mut nodes, format) = (parsed.url, parsed.nodes, parsed.format);
+            if expand_cdn {
+                nodes = expand_cdn_hosts(nodes);
+            }
+            let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            let elapsed = parse_start.elapsed().as_secs_f64();
+            bar.inc();
+    
+            bar.line(false, format!("Parse [{}/{}] {} -> {} nodes via {} (took {:.1}s)", 
+                             count, parse_tasks_len, url, nodes.len(), format, elapsed));
+    
+            let nodes: Vec<Node> = nodes.into_iter().map(|node| node.with_source(&url)).collect();
+            (nodes, Some(diagnostic))
+        }));
+    }
+    
+    let mut parsed_nodes = Vec::new();
+    let mut diagnostics = Vec::new();
+    for joined in join_until(parse_tasks, deadline).await {
+        let (nodes, diagnostic) = joined?;
+        parsed_nodes.extend(nodes);
+        diagnostics.extend(diagnostic);
+    }
+    parse_bar.finish();
+    
+    if let Some(path) = &args.diagnostics {
+        write_diagnostics(path, &diagnostics).await?;
+        info!("🩺 Wrote parse diagnostics for {} subscriptions to {}", diagnostics.len(), path);
+    }
+    
+    if args.dup_stats {
+        let stats = DupStats::new(&parsed_nodes, DUP_STATS_TOP);
+        info!("📑 Parsed {} nodes, {} unique ({} duplicates dropped)", stats.total, stats.unique, stats.duplicates());
+        for (node, count) in &stats.top {
+            info!("   {}x {}:{}", count, node.authority_host(), node.port);
+        }
+    }
+    // keep parse order so --limit's first N are the first ones parsed
+    let mut seen_nodes = HashSet::new();
+    let all_nodes: Vec<Node> = parsed_nodes.into_iter().filter(|node| seen_nodes.insert(node.clone())).collect();
+    info!("🎯 Total unique nodes parsed: {}", all_nodes.len());
+    eta.phase_done(Phase::Parse, parse_tasks_len, phase_start.elapsed().as_secs_f64());
+    Ok((working_urls, total_urls, total_eta, eta, all_nodes))
+}
+
+fn log_eta(eta: &EtaEstimator) {
+    info!("⏳ Updated estimate: ~{} remaining (total ~{})", format_duration(eta.remaining()), format_duration(eta.total()));
+}
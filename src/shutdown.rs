@@ -0,0 +1,29 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{error, warn};
+
+/// First Ctrl-C sets the returned flag so the phases stop spawning work and the
+/// partial results get written. A second Ctrl-C exits on the spot.
+pub fn install_ctrl_c_handler() -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    let handler_flag = flag.clone();
+    
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        handler_flag.store(true, Ordering::SeqCst);
+        warn!("🛑 Ctrl-C received, finishing in-flight work and writing partial results (Ctrl-C again to force quit)");
+        
+        if tokio::signal::ctrl_c().await.is_ok() {
+            error!("💥 Forced exit");
+            std::process::exit(130);
+        }
+    });
+    
+    flag
+}
+
+pub fn is_shutting_down(flag: &AtomicBool) -> bool {
+    flag.load(Ordering::SeqCst)
+}
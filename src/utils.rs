@@ -12,18 +12,60 @@ pub fn format_duration(seconds: f64) -> String {
     }
 }
 
-pub fn estimate_total_time(num_urls: usize) -> (f64, f64) {
-    let num_urls = num_urls as f64;
-    let url_phase = (num_urls * EST_URL_CHECK_TIME) / MAX_IO_WORKERS as f64;
-    let fetch_phase = (num_urls * 0.7 * EST_FETCH_TIME) / MAX_IO_WORKERS as f64;
-    let parse_phase = (num_urls * 0.7 * EST_PARSE_TIME) / MAX_PARSE_WORKERS as f64;
-    let node_phase = (num_urls * 0.7 * EST_NODES_PER_SUB * EST_NODE_TIME) / MAX_IO_WORKERS as f64;
+
+
+/// Nearest-rank percentile (`p` in 0..=100), `None` for an empty slice
+pub fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+pub fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { part as f64 * 100.0 / total as f64 }
+}
+
+/// Generators can't agree on base64: try standard and URL-safe, with and without padding
+pub fn decode_flexible(input: &str) -> Option<Vec<u8>> {
+    use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+    use base64::Engine;
     
-    let total = url_phase + fetch_phase + parse_phase + node_phase;
-    (total, url_phase + fetch_phase + parse_phase)
+    let input = input.trim();
+    [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+        .iter()
+        .find_map(|engine| engine.decode(input).ok())
 }
 
+/// Lowercases, trims and drops a single trailing dot so `Example.COM.` and `example.com`
+/// end up as the same node. IPv6 literals keep their zone id as-is and percent-encoded
+/// hosts are only trimmed.
+pub fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    if host.contains(':') {
+        return match host.split_once('%') {
+            Some((addr, zone)) => format!("{}%{}", addr.to_lowercase(), zone),
+            None => host.to_lowercase(),
+        };
+    }
+    if host.contains('%') {
+        return host.to_string();
+    }
+    host.strip_suffix('.').unwrap_or(host).to_lowercase()
+}
 
+/// Port 0 and anything past 65535 aren't something we can connect to
+pub fn valid_port(port: u64) -> Option<u16> {
+    u16::try_from(port).ok().filter(|&p| p != 0)
+}
+
+/// `valid_port` for a port that's still text
+pub fn parse_port(port: &str) -> Option<u16> {
+    port.trim().parse::<u64>().ok().and_then(valid_port)
+}
 
 pub fn safe_limit_text(text: &str) -> String {
     let mut result = text;
@@ -41,3 +83,47 @@ pub fn safe_limit_text(text: &str) -> String {
         result.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_port() {
+        assert_eq!(valid_port(0), None);
+        assert_eq!(valid_port(1), Some(1));
+        assert_eq!(valid_port(65535), Some(65535));
+        assert_eq!(valid_port(65536), None);
+        assert_eq!(parse_port("0"), None);
+        assert_eq!(parse_port("65535"), Some(65535));
+        assert_eq!(parse_port("70000"), None);
+        assert_eq!(parse_port("http"), None);
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host(" Example.COM. "), "example.com");
+        assert_eq!(normalize_host("2001:DB8::1"), "2001:db8::1");
+        assert_eq!(normalize_host("fe80::1%Eth0"), "fe80::1%Eth0");
+        assert_eq!(normalize_host("a%2Eexample.com"), "a%2Eexample.com");
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values = [50.0, 10.0, 40.0, 20.0, 30.0];
+        assert_eq!(percentile(&values, 50.0), Some(30.0));
+        assert_eq!(percentile(&values, 95.0), Some(50.0));
+        assert_eq!(percentile(&values, 0.0), Some(10.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_decode_flexible_variants() {
+        // "??>" encodes to "Pz8+" standard, "Pz8-" url-safe
+        assert_eq!(decode_flexible("Pz8+"), Some(b"??>".to_vec()));
+        assert_eq!(decode_flexible("Pz8-"), Some(b"??>".to_vec()));
+        assert_eq!(decode_flexible("aGk="), Some(b"hi".to_vec()));
+        assert_eq!(decode_flexible("aGk"), Some(b"hi".to_vec()));
+        assert_eq!(decode_flexible("not base64!"), None);
+    }
+}
@@ -0,0 +1,29 @@
+use clap::Parser;
+
+use proxy_yoink_er::config::{Args, CheckMode};
+
+// Its own test binary: env vars are process-wide and would leak into other tests' Args
+#[test]
+fn test_args_from_env() {
+    // SAFETY: the only test in this binary, nothing else reads the environment concurrently
+    unsafe {
+        std::env::set_var("PY_INPUT", "links.txt");
+        std::env::set_var("PY_MAX_IO_WORKERS", "7");
+        std::env::set_var("PY_URL_OUT", "env_urls.md");
+        std::env::set_var("PY_CHECK_MODE", "http");
+        std::env::set_var("PY_NO_NODE_TEST", "true");
+    }
+    
+    let args = Args::try_parse_from(["proxy-yoink-er"]).unwrap();
+    assert_eq!(args.input, vec!["links.txt"]);
+    assert_eq!(args.max_io_workers, 7);
+    assert_eq!(args.url_out, "env_urls.md");
+    assert_eq!(args.check_mode, CheckMode::Http);
+    assert!(args.no_node_test);
+    
+    // the command line still wins
+    let args = Args::try_parse_from(["proxy-yoink-er", "other.txt", "--max-io-workers", "3"]).unwrap();
+    assert_eq!(args.input, vec!["other.txt"]);
+    assert_eq!(args.max_io_workers, 3);
+    assert_eq!(args.url_out, "env_urls.md");
+}
@@ -0,0 +1,302 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+
+use proxy_yoink_er::{run, ProxyYoinkerError, RunConfig};
+
+/// Answers every request with a one-node subscription. The same port doubles as the
+/// "node" the subscription points at, so the TCP check has something to connect to.
+async fn serve_subscription() -> SocketAddr {
+    serve_body(|port| format!("vless://id@127.0.0.1:{}?security=none#local\n", port)).await
+}
+
+/// Answers every request with `body(own port)`
+async fn serve_body(body: impl FnOnce(u16) -> String) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let body = body(addr.port());
+    
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let body = body.clone();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                if n == 0 {
+                    return;
+                }
+                let head = String::from_utf8_lossy(&buf[..n]).starts_with("HEAD");
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    if head { "" } else { body.as_str() }
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    
+    addr
+}
+
+/// Takes `delay` to answer anything, with an empty 200
+async fn serve_slowly(delay: Duration) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                sleep(delay).await;
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+            });
+        }
+    });
+    
+    addr
+}
+
+#[tokio::test]
+async fn test_run_returns_structured_report() {
+    let addr = serve_subscription().await;
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let input = dir.join("links.txt");
+    std::fs::write(&input, format!("http://{}/sub\n", addr)).unwrap();
+    
+    let mut config = RunConfig::for_input(input.to_str().unwrap()).unwrap();
+    config.url_out = dir.join("urls.md").to_str().unwrap().to_string();
+    config.node_out = dir.join("nodes.md").to_str().unwrap().to_string();
+    config.keep_local = true;
+    
+    let report = run(config).await.unwrap();
+    
+    assert!(!report.interrupted);
+    assert_eq!(report.working_urls.len(), 1);
+    assert_eq!(report.working_urls[0].url, format!("http://{}/sub", addr));
+    assert_eq!(report.node_results.len(), 1);
+    assert_eq!(report.node_results[0].node.host, "127.0.0.1");
+    assert_eq!(report.node_results[0].node.port, addr.port());
+    assert!(report.node_results[0].is_working());
+    assert_eq!(report.summary.reachable_nodes, 1);
+}
+
+#[tokio::test]
+async fn test_timeout_total_writes_partial_results() {
+    let addr = serve_slowly(Duration::from_secs(2)).await;
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let input = dir.join("links.txt");
+    let links: String = (0..6).map(|i| format!("http://{}/sub{}\n", addr, i)).collect();
+    std::fs::write(&input, links).unwrap();
+    
+    let mut config = RunConfig::for_input(input.to_str().unwrap()).unwrap();
+    config.url_out = dir.join("urls.md").to_str().unwrap().to_string();
+    config.node_out = dir.join("nodes.md").to_str().unwrap().to_string();
+    config.max_io_workers = 1;
+    config.url_timeout = 30;
+    config.retries = 0;
+    config.timeout_total = Some(1);
+    
+    // six URLs one at a time would take 12s, the deadline lets only the in-flight one finish
+    let start = Instant::now();
+    let report = run(config).await.unwrap();
+    let elapsed = start.elapsed();
+    let url_report_written = dir.join("urls.md").exists() && dir.join("nodes.md").exists();
+    
+    assert!(report.timed_out);
+    assert!(!report.interrupted);
+    assert!(elapsed < Duration::from_secs(5), "took {:?}", elapsed);
+    assert!(report.working_urls.len() <= 1);
+    assert!(url_report_written);
+}
+
+/// Answers HEAD with an empty 200 but never answers GET, so the URL check passes and the fetch hangs
+async fn serve_hanging_get() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                if String::from_utf8_lossy(&buf[..n]).starts_with("HEAD") {
+                    let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+                } else {
+                    std::future::pending::<()>().await;
+                }
+            });
+        }
+    });
+    
+    addr
+}
+
+#[tokio::test]
+async fn test_timeout_total_aborts_hung_fetches() {
+    let addr = serve_hanging_get().await;
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let input = dir.join("links.txt");
+    std::fs::write(&input, format!("http://{}/sub\n", addr)).unwrap();
+    
+    let mut config = RunConfig::for_input(input.to_str().unwrap()).unwrap();
+    config.url_out = dir.join("urls.md").to_str().unwrap().to_string();
+    config.node_out = dir.join("nodes.md").to_str().unwrap().to_string();
+    config.url_timeout = 600;
+    config.retries = 0;
+    config.timeout_total = Some(1);
+    
+    // the fetch would sit there for --url-timeout, the deadline has to cut it off
+    let start = Instant::now();
+    let report = run(config).await.unwrap();
+    let elapsed = start.elapsed();
+    let reports_written = dir.join("urls.md").exists() && dir.join("nodes.md").exists();
+    
+    assert!(report.timed_out);
+    assert!(elapsed < Duration::from_secs(5), "took {:?}", elapsed);
+    assert_eq!(report.working_urls.len(), 1);
+    assert!(report.node_results.is_empty());
+    assert!(reports_written);
+}
+
+async fn parsed_hosts(keep_local: bool) -> Vec<String> {
+    let addr = serve_body(|_| "vless://id@127.0.0.1:1080#a\nvless://id@localhost:1080#b\nvless://id@node.example.com:443#c\n".to_string()).await;
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let input = dir.join("links.txt");
+    std::fs::write(&input, format!("http://{}/sub\n", addr)).unwrap();
+    
+    let mut config = RunConfig::for_input(input.to_str().unwrap()).unwrap();
+    config.url_out = dir.join("urls.md").to_str().unwrap().to_string();
+    config.node_out = dir.join("nodes.md").to_str().unwrap().to_string();
+    config.no_node_test = true;
+    config.keep_local = keep_local;
+    
+    let report = run(config).await.unwrap();
+    
+    let mut hosts: Vec<String> = report.node_results.into_iter().map(|r| r.node.host).collect();
+    hosts.sort();
+    hosts
+}
+
+#[tokio::test]
+async fn test_local_hosts_skipped_unless_kept() {
+    assert_eq!(parsed_hosts(false).await, vec!["node.example.com"]);
+    assert_eq!(parsed_hosts(true).await, vec!["127.0.0.1", "localhost", "node.example.com"]);
+}
+
+#[tokio::test]
+async fn test_limit_caps_tested_nodes() {
+    let addr = serve_body(|_| (1..=5).map(|i| format!("vless://id@node{}.example.com:443#n{}\n", i, i)).collect()).await;
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let input = dir.join("links.txt");
+    std::fs::write(&input, format!("http://{}/sub\n", addr)).unwrap();
+    
+    let mut config = RunConfig::for_input(input.to_str().unwrap()).unwrap();
+    config.url_out = dir.join("urls.md").to_str().unwrap().to_string();
+    config.node_out = dir.join("nodes.md").to_str().unwrap().to_string();
+    config.no_node_test = true;
+    config.limit = Some(3);
+    
+    let report = run(config).await.unwrap();
+    
+    let hosts: Vec<&str> = report.node_results.iter().map(|r| r.node.host.as_str()).collect();
+    assert_eq!(hosts, vec!["node1.example.com", "node2.example.com", "node3.example.com"]);
+}
+
+#[tokio::test]
+async fn test_output_dir_collects_reports() {
+    let addr = serve_subscription().await;
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let input = dir.join("links.txt");
+    std::fs::write(&input, format!("http://{}/sub\n", addr)).unwrap();
+    let out = dir.join("reports");
+    
+    let mut config = RunConfig::for_input(input.to_str().unwrap()).unwrap();
+    config.output_dir = Some(out.to_str().unwrap().to_string());
+    
+    run(config).await.unwrap();
+    let mut written: Vec<String> = std::fs::read_dir(&out).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    written.sort();
+    
+    assert_eq!(written, vec!["clash.yaml", "node_latencies.md", "raw_links.txt", "summary.md", "working_links.md"]);
+}
+
+#[tokio::test]
+async fn test_missing_input_is_an_input_error() {
+    let tmp = tempfile::tempdir().unwrap();
+    let missing = tmp.path().join("missing").join("links.txt");
+    let missing = missing.to_str().unwrap();
+    
+    match run(RunConfig::for_input(missing).unwrap()).await {
+        Err(ProxyYoinkerError::Input { path, source }) => {
+            assert_eq!(path, missing);
+            assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+        }
+        other => panic!("expected an input error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_stream_applies_max_latency() {
+    // a working node on the server's own port and one nothing listens on
+    let addr = serve_body(|port| format!("vless://id@127.0.0.1:{}#up\nvless://id@127.0.0.1:1#down\n", port)).await;
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let input = dir.join("links.txt");
+    std::fs::write(&input, format!("http://{}/sub\n", addr)).unwrap();
+    
+    let mut config = RunConfig::for_input(input.to_str().unwrap()).unwrap();
+    config.url_out = dir.join("urls.md").to_str().unwrap().to_string();
+    config.node_out = dir.join("nodes.md").to_str().unwrap().to_string();
+    config.keep_local = true;
+    config.retries = 0;
+    config.stream = true;
+    config.max_latency = Some(5000.0);
+    
+    let report = run(config).await.unwrap();
+    let streamed = std::fs::read_to_string(dir.join("nodes.md")).unwrap();
+    
+    assert_eq!(report.node_results.len(), 2);
+    assert!(streamed.contains(&format!("| 127.0.0.1 | {} |", addr.port())));
+    assert!(!streamed.contains("| 127.0.0.1 | 1 |"));
+}
+
+#[tokio::test]
+async fn test_inventory_ignores_max_latency() {
+    let addr = serve_body(|_| "vless://id@node.example.com:443#a\n".to_string()).await;
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let input = dir.join("links.txt");
+    std::fs::write(&input, format!("http://{}/sub\n", addr)).unwrap();
+    
+    let mut config = RunConfig::for_input(input.to_str().unwrap()).unwrap();
+    config.url_out = dir.join("urls.md").to_str().unwrap().to_string();
+    config.node_out = dir.join("nodes.md").to_str().unwrap().to_string();
+    config.no_node_test = true;
+    config.max_latency = Some(100.0);
+    
+    run(config).await.unwrap();
+    let inventory = std::fs::read_to_string(dir.join("nodes.md")).unwrap();
+    
+    assert!(inventory.contains("| Host | Port | Protocol |"));
+    assert!(inventory.contains("| node.example.com | 443 | vless |"));
+}
+
+#[test]
+fn test_for_input_reports_bad_arguments() {
+    // clap would print usage and exit here with parse_from
+    match RunConfig::for_input("--no-such-flag") {
+        Err(ProxyYoinkerError::Config(message)) => assert!(message.contains("--no-such-flag"), "{}", message),
+        Err(other) => panic!("expected a config error, got {:?}", other),
+        Ok(_) => panic!("expected an error"),
+    }
+}